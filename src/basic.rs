@@ -1,5 +1,15 @@
+pub mod code_generator;
+pub mod config;
 pub mod database;
+pub mod datetime;
+pub mod de;
+pub mod fixed;
+pub mod index;
 pub mod parser;
+pub mod query;
 pub mod raw_table;
-pub mod config;
-pub mod code_generator;
\ No newline at end of file
+pub mod reference;
+pub mod schema;
+pub mod stream;
+pub mod type_expr;
+pub mod value;
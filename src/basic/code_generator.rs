@@ -1 +1,263 @@
+//! 各种语言的代码生成器, 按名字注册, 导出时按名字选
+
 pub mod csharp;
+pub mod helpers;
+pub mod typescript;
+pub mod unicode;
+
+use std::{
+  path::{Path, PathBuf},
+  str::FromStr,
+};
+
+use anyhow::Result;
+use handlebars::{Handlebars, no_escape};
+use rayon::prelude::*;
+
+use crate::{
+  IndexMap,
+  basic::{
+    code_generator::{csharp::CSharpGenerator, typescript::TypeScriptGenerator},
+    config::DictLayout,
+    database::Database,
+  },
+};
+use error::Error::*;
+
+/// 生成器自己的选项放在实现类型里, 注册时给默认值, 再用 [CodeGenerator::set_option] 一个个改
+pub trait CodeGenerator: Send + Sync {
+  /// 命令行 `--lang` 里用的名字
+  fn name(&self) -> &str;
+  /// 命令行 `--lang-opt 名字.选项=值` 里的选项
+  fn set_option(&mut self, key: &str, _value: &str) -> Result<()> {
+    Err(生成器选项不存在(self.name().to_string(), key.to_string()).into())
+  }
+  /// 生成的代码只认一种字典写法时返回它, 命令行没写 `--dict-layout` 时用
+  fn dict_layout(&self) -> Option<DictLayout> {
+    None
+  }
+  /// `target` 是这个生成器自己的输出目录
+  fn generate(&self, database: &Database, target: &Path) -> Result<()>;
+}
+
+/// 同名的生成器后注册的覆盖先注册的
+#[derive(Default)]
+pub struct Registry {
+  generators: IndexMap<String, Box<dyn CodeGenerator>>,
+}
+
+impl Registry {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// 自带的 `csharp` 和 `typescript`, 都用默认选项
+  pub fn with_builtin() -> Self {
+    let mut registry = Self::new();
+    registry.register(CSharpGenerator::default());
+    registry.register(TypeScriptGenerator::default());
+    registry
+  }
+
+  pub fn register(&mut self, generator: impl CodeGenerator + 'static) {
+    self
+      .generators
+      .insert(generator.name().to_string(), Box::new(generator));
+  }
+
+  pub fn get(&self, name: &str) -> Option<&dyn CodeGenerator> {
+    self.generators.get(name).map(|g| &**g)
+  }
+
+  /// `名字.选项=值`, 例: `csharp.alias=using`. 只写 `名字.选项` 时值是 `true`
+  pub fn set_option(&mut self, option: &str) -> Result<()> {
+    let (key, value) = option.split_once('=').unwrap_or((option, "true"));
+    let (name, key) = key
+      .split_once('.')
+      .ok_or_else(|| 生成器选项格式错误(option.to_string()))?;
+    let names = self.names().collect::<Vec<_>>().join(", ");
+    let generator = self
+      .generators
+      .get_mut(name.trim())
+      .ok_or_else(|| 代码生成器不存在(name.to_string(), names))?;
+    generator.set_option(key.trim(), value.trim())
+  }
+
+  pub fn names(&self) -> impl Iterator<Item = &str> {
+    self.generators.keys().map(String::as_str)
+  }
+}
+
+/// 用 [FromStr] 解析选项的值, 给 [CodeGenerator::set_option] 用
+pub fn parse_option<T: FromStr>(name: &str, key: &str, value: &str) -> Result<T> {
+  value
+    .parse()
+    .map_err(|_| 生成器选项的值错误(name.to_string(), key.to_string(), value.to_string()).into())
+}
+
+/// 内置的模板编进程序里, 不管从哪里运行都能找到.
+/// 再用 `dir` 里同名的 `.hbs` 覆盖, `dir` 里多出来的模板可以用 `{{> 名字}}` 当 partial 用.
+/// 所有模板本身也都能当 partial 用
+pub fn new_registry<'a>(builtin: &[(&str, &str)], dir: Option<&Path>) -> Result<Handlebars<'a>> {
+  let mut reg = Handlebars::new();
+  reg.register_escape_fn(no_escape);
+  helpers::register(&mut reg);
+  for (name, content) in builtin {
+    reg.register_template_string(name, content)?;
+  }
+  if let Some(dir) = dir {
+    for entry in dir
+      .read_dir()
+      .map_err(|e| anyhow::Error::from(e).context(format!("模板目录 {} 读取失败", dir.display())))?
+    {
+      let path = entry?.path();
+      if path.is_file() && path.extension().is_some_and(|e| e == "hbs") {
+        let stem = path
+          .file_stem()
+          .unwrap()
+          .to_str()
+          .ok_or(模板文件名含有非Unicode字符)?;
+        reg.register_template_file(stem, &path)?;
+      }
+    }
+  }
+  Ok(reg)
+}
+
+impl Database {
+  /// `targets` 是 (生成器名字, 输出目录), 几个生成器同时跑
+  pub fn generate_code(&self, registry: &Registry, targets: &[(String, PathBuf)]) -> Result<()> {
+    let generators = targets
+      .iter()
+      .map(|(name, target)| {
+        let generator = registry.get(name).ok_or_else(|| {
+          代码生成器不存在(
+            name.clone(),
+            registry.names().collect::<Vec<_>>().join(", "),
+          )
+        })?;
+        Ok((generator, target))
+      })
+      .collect::<Result<Vec<_>>>()?;
+    generators
+      .into_par_iter()
+      .try_for_each(|(generator, target)| {
+        generator
+          .generate(self, target)
+          .map_err(|e| e.context(format!("{} 代码生成失败", generator.name())))
+      })
+  }
+}
+
+pub mod error {
+  use thiserror::Error;
+
+  #[derive(Debug, Error)]
+  pub enum Error {
+    #[error("代码生成器不存在: {0}, 可用的有: {1}")]
+    代码生成器不存在(String, String),
+    #[error("模板文件名含有非Unicode字符")]
+    模板文件名含有非Unicode字符,
+    #[error("{0} 没有选项 {1}")]
+    生成器选项不存在(String, String),
+    #[error("生成器选项的格式是 `名字.选项=值`: {0}")]
+    生成器选项格式错误(String),
+    #[error("{0} 的选项 {1} 的值错误: {2}")]
+    生成器选项的值错误(String, String, String),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::Path;
+
+  use anyhow::Result;
+
+  use crate::basic::{
+    code_generator::{
+      CodeGenerator, Registry,
+      csharp::{AliasStyle, CSharpGenerator},
+      new_registry,
+    },
+    config::DictLayout,
+    database::Database,
+  };
+
+  struct Count;
+
+  impl CodeGenerator for Count {
+    fn name(&self) -> &str {
+      "count"
+    }
+    fn generate(&self, database: &Database, target: &Path) -> Result<()> {
+      std::fs::create_dir_all(target)?;
+      std::fs::write(
+        target.join("count.txt"),
+        database.tables().count().to_string(),
+      )?;
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_registry() -> Result<()> {
+    let mut db = Database::new();
+    db.load_project("./test/proj/")?;
+    let mut registry = Registry::with_builtin();
+    registry.register(Count);
+    assert_eq!(
+      registry.names().collect::<Vec<_>>(),
+      ["csharp", "typescript", "count"]
+    );
+
+    let out = std::env::temp_dir().join("code_generator_test_registry");
+    db.generate_code(&registry, &[("count".to_string(), out.clone())])?;
+    assert_eq!(
+      std::fs::read_to_string(out.join("count.txt"))?,
+      db.tables().count().to_string()
+    );
+    assert!(
+      db.generate_code(&registry, &[("java".to_string(), out)])
+        .is_err()
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn test_set_option() -> Result<()> {
+    let mut registry = Registry::with_builtin();
+    registry.register(Count);
+    registry.set_option("csharp.alias=using")?;
+    registry.set_option("csharp.lazy")?;
+    assert_eq!(
+      registry.set_option("count.x=1").unwrap_err().to_string(),
+      "count 没有选项 x"
+    );
+    assert!(registry.set_option("csharp.alias=x").is_err());
+    assert!(registry.set_option("csharp.lazy=1").is_err());
+    assert!(registry.set_option("java.x=1").is_err());
+    assert!(registry.set_option("alias=using").is_err());
+
+    let mut csharp = CSharpGenerator::default();
+    csharp.set_option("alias", "using")?;
+    csharp.set_option("profile", "unity")?;
+    csharp.set_option("async", "true")?;
+    assert_eq!(csharp.options.alias_style, AliasStyle::Using);
+    assert!(csharp.options.async_load);
+    assert_eq!(csharp.dict_layout(), Some(DictLayout::Entries));
+    Ok(())
+  }
+
+  #[test]
+  fn test_template_override() -> Result<()> {
+    let dir = std::env::temp_dir().join("code_generator_test_template_override");
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join("b.hbs"), "[{{> part}}]")?;
+    std::fs::write(dir.join("part.hbs"), "{{snake_case name}}")?;
+    let reg = new_registry(&[("a", "{{name}}"), ("b", "内置")], Some(&dir))?;
+    let ctx = serde_json::json!({"name": "ItemId"});
+    assert_eq!(reg.render("a", &ctx)?, "ItemId");
+    assert_eq!(reg.render("b", &ctx)?, "[item_id]");
+    Ok(())
+  }
+}
@@ -1,63 +1,254 @@
 use std::{
   fs::{create_dir, create_dir_all, write},
   ops::Not,
-  path::Path,
+  path::{Path, PathBuf},
 };
 
 use anyhow::Result;
-use ego_tree::{NodeId, Tree};
-use handlebars::{Handlebars, no_escape};
+use ego_tree::NodeId;
+use handlebars::Handlebars;
 use serde::Serialize;
-use serde_json::{Map, Value};
+use strum::{Display, EnumString};
 
 use crate::basic::{
-  config::{self, OptionJoin},
-  database::{Database, Type},
+  code_generator::{self, CodeGenerator, parse_option},
+  config::{self, DictLayout, OptionJoin},
+  database::{Data, Database, Type},
+  reference::RefShape,
 };
+use error::Error::*;
+use naming::{Casing, NamingOptions, Scope};
+
+pub mod naming;
+
+/// 类型别名在 C# 里的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum AliasStyle {
+  /// 直接写成实际类型
+  #[default]
+  Inline,
+  /// `global using ItemId = int;`, 所有别名共用一个命名空间, 不能重名
+  Using,
+  /// 包一层 struct, 不能和实际类型混用
+  Struct,
+}
+
+/// 生成的数据类型能不能改
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Mutability {
+  /// 公开字段, 集合是 `T[]` 和 `Dictionary`
+  #[default]
+  Mutable,
+  /// `record`, 属性只能 `init`, 集合是 `IReadOnlyList` 和 `IReadOnlyDictionary`.
+  /// 别名和定点数本来就是 `readonly struct`. 元组还是 `ValueTuple`, 但属性和集合里取出来的是副本, 改不到数据
+  ReadOnly,
+  /// 和 `read_only` 一样, 集合换成 `ImmutableArray` 和 `ImmutableDictionary`
+  Immutable,
+}
+
+/// 生成的代码在哪里用
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Profile {
+  /// .NET 8 以上, 用 `System.Text.Json`
+  #[default]
+  Dotnet,
+  /// Unity 的 C# 9, 命名空间用花括号, 字典是 `SerializableDictionary`.
+  /// 数据要用 [config::DictLayout::Entries] 导出
+  Unity,
+}
+
+/// Unity 下用哪个 json 库
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum UnityJson {
+  /// `com.unity.nuget.newtonsoft-json`
+  #[default]
+  Newtonsoft,
+  /// 自带的, 不用装包, 但只支持公开字段, 不支持元组, 可空值类型, 时间, 定点数和嵌套列表
+  JsonUtility,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CSharpOptions {
+  pub alias_style: AliasStyle,
+  pub mutability: Mutability,
+  /// 生成 `JsonSerializerContext` 和不用反射的转换器, 给 NativeAOT 和裁剪用
+  pub source_gen: bool,
+  /// 表第一次用到时才读, 另外生成 `Cfg.Preload`.
+  /// 引用列找不到的主键也要等表第一次用到时才报错, 加载界面里调用 `Cfg.Preload` 可以一起报出来
+  pub lazy: bool,
+  /// 另外生成 `IDataAccessAsync` 和 `Cfg.LoadAsync`
+  pub async_load: bool,
+  /// 每张表包一个类, 有 `Get`, `TryGet` 和标注了 `index` 或 `unique` 的列的查找方法
+  pub table_class: bool,
+  /// 另外生成 `Cfg.Reload`, `Cfg.ReloadTable` 和 `Cfg.OnReloaded`, 运行时重新读表
+  pub hot_reload: bool,
+  /// 里面的 `.hbs` 覆盖同名的内置模板
+  pub template_dir: Option<PathBuf>,
+  pub naming: NamingOptions,
+  pub profile: Profile,
+  /// 只在 [Profile::Unity] 下有用
+  pub unity_json: UnityJson,
+  /// 每张表生成一个 `ScriptableObject` 和编辑器里的导入菜单, 只在 [Profile::Unity] 下有用
+  pub scriptable_object: bool,
+}
+
+/// 内置模板, 见 [code_generator::new_registry]
+const TEMPLATES: &[(&str, &str)] = &[
+  (
+    "access",
+    include_str!("../../../templates/csharp/access.hbs"),
+  ),
+  ("alias", include_str!("../../../templates/csharp/alias.hbs")),
+  (
+    "aliases",
+    include_str!("../../../templates/csharp/aliases.hbs"),
+  ),
+  ("cfg", include_str!("../../../templates/csharp/cfg.hbs")),
+  ("class", include_str!("../../../templates/csharp/class.hbs")),
+  (
+    "editor_importer",
+    include_str!("../../../templates/csharp/editor_importer.hbs"),
+  ),
+  ("fixed", include_str!("../../../templates/csharp/fixed.hbs")),
+  (
+    "json_context",
+    include_str!("../../../templates/csharp/json_context.hbs"),
+  ),
+  ("mod", include_str!("../../../templates/csharp/mod.hbs")),
+  (
+    "namespace_begin",
+    include_str!("../../../templates/csharp/namespace_begin.hbs"),
+  ),
+  (
+    "namespace_end",
+    include_str!("../../../templates/csharp/namespace_end.hbs"),
+  ),
+  (
+    "scriptable_object",
+    include_str!("../../../templates/csharp/scriptable_object.hbs"),
+  ),
+  ("table", include_str!("../../../templates/csharp/table.hbs")),
+  (
+    "usings",
+    include_str!("../../../templates/csharp/usings.hbs"),
+  ),
+  (
+    "unity_util",
+    include_str!("../../../templates/csharp/unity_util.hbs"),
+  ),
+  ("util", include_str!("../../../templates/csharp/util.hbs")),
+];
+
+/// 注册到 [super::Registry] 里的 `csharp`, 每次生成时读一遍模板
+#[derive(Debug, Clone, Default)]
+pub struct CSharpGenerator {
+  pub options: CSharpOptions,
+}
+
+impl CodeGenerator for CSharpGenerator {
+  fn name(&self) -> &str {
+    "csharp"
+  }
+  /// 选项名和 [CSharpOptions] 的字段对应, 大小写的三个是 `class_case`, `field_case`, `namespace_case`
+  fn set_option(&mut self, key: &str, value: &str) -> Result<()> {
+    let o = &mut self.options;
+    match key {
+      "alias" => o.alias_style = parse_option("csharp", key, value)?,
+      "mutability" => o.mutability = parse_option("csharp", key, value)?,
+      "source_gen" => o.source_gen = parse_option("csharp", key, value)?,
+      "lazy" => o.lazy = parse_option("csharp", key, value)?,
+      "async" => o.async_load = parse_option("csharp", key, value)?,
+      "table_class" => o.table_class = parse_option("csharp", key, value)?,
+      "hot_reload" => o.hot_reload = parse_option("csharp", key, value)?,
+      "class_case" => o.naming.class = parse_option("csharp", key, value)?,
+      "field_case" => o.naming.field = parse_option("csharp", key, value)?,
+      "namespace_case" => o.naming.namespace = parse_option("csharp", key, value)?,
+      "profile" => o.profile = parse_option("csharp", key, value)?,
+      "unity_json" => o.unity_json = parse_option("csharp", key, value)?,
+      "scriptable_object" => o.scriptable_object = parse_option("csharp", key, value)?,
+      _ => {
+        return Err(
+          code_generator::error::Error::生成器选项不存在(
+            "csharp".to_string(),
+            key.to_string(),
+          )
+          .into(),
+        );
+      }
+    }
+    Ok(())
+  }
+  /// Unity 那边的字典只认列表
+  fn dict_layout(&self) -> Option<DictLayout> {
+    (self.options.profile == Profile::Unity).then_some(DictLayout::Entries)
+  }
+  fn generate(&self, database: &Database, target: &Path) -> Result<()> {
+    CSharp::try_new(database, self.options.clone())?.generate(target)
+  }
+}
 
 pub struct CSharp<'a> {
   pub reg: Handlebars<'a>,
   pub database: &'a Database,
+  pub options: CSharpOptions,
   pub common_env: CommonEnv,
 }
 
 impl<'a> CSharp<'a> {
-  pub fn try_new(database: &'a Database) -> Result<Self> {
-    let mut reg = Handlebars::new();
-    reg.register_escape_fn(no_escape);
-
-    let template_folder = Path::new("./templates/csharp/");
-    for entry in template_folder.read_dir()? {
-      let tpath = entry?.path();
-      if tpath.is_file() {
-        let name = tpath.file_name().unwrap().to_str().unwrap();
-        let stem = tpath.file_stem().unwrap().to_str().unwrap();
-        reg.register_template_file(stem, template_folder.join(name))?;
-      }
+  pub fn try_new(database: &'a Database, options: CSharpOptions) -> Result<Self> {
+    let reg = code_generator::new_registry(TEMPLATES, options.template_dir.as_deref())?;
+    let immutable = options.mutability != Mutability::Mutable;
+    let source_gen = options.source_gen;
+    let lazy = options.lazy;
+    let async_load = options.async_load;
+    let table_class = options.table_class;
+    let hot_reload = options.hot_reload;
+    let unity = options.profile == Profile::Unity;
+    let newtonsoft = unity && options.unity_json == UnityJson::Newtonsoft;
+    let mut class_usings = vec![
+      "System".to_string(),
+      "System.Collections.Generic".to_string(),
+    ];
+    let mut mod_usings = class_usings.clone();
+    if !unity {
+      mod_usings.push("System.Text.Json.Serialization".to_string());
+      mod_usings.push("System.Text.Json".to_string());
+      class_usings.push("System.Text.Json.Serialization".to_string());
+    } else if newtonsoft {
+      class_usings.push("Newtonsoft.Json".to_string());
+    }
+    if async_load {
+      mod_usings.push("System.Threading".to_string());
+      mod_usings.push("System.Threading.Tasks".to_string());
     }
 
     let project_namespace = Some("__Gen.Cfg");
     let common_namespace_name = "Common";
     let data_type_namespace_name = "Types";
-    let mut res = CSharp {
+    let res = CSharp {
       reg,
       database,
+      options,
       common_env: CommonEnv {
         project_namespace: project_namespace.map(|s| s.to_string()),
         cfg_class_name: "Cfg".to_string(),
         mod_class_name: "Mod".to_string(),
+        immutable,
+        source_gen,
+        lazy,
+        async_load,
+        table_class,
+        hot_reload,
+        block_namespace: unity,
+        unity,
+        newtonsoft,
 
-        mod_usings: vec![
-          "System".to_string(),
-          "System.Collections.Generic".to_string(),
-          "System.Text.Json.Serialization".to_string(),
-          "System.Text.Json".to_string(),
-        ],
-        class_usings: vec![
-          "System".to_string(),
-          "System.Collections.Generic".to_string(),
-          "System.Text.Json.Serialization".to_string(),
-        ],
+        mod_usings,
+        class_usings,
         common_namespace_name: common_namespace_name.to_string(),
         common_namespace: [project_namespace, Some(common_namespace_name)]
           .option_join(NAMESPACE_SEPARATOR),
@@ -70,11 +261,19 @@ impl<'a> CSharp<'a> {
     Ok(res)
   }
   pub fn generate(&self, target: impl AsRef<Path>) -> Result<()> {
+    if self.common_env.unity {
+      self.check_unity()?;
+    }
     if !target.as_ref().exists() {
       create_dir_all(target.as_ref())?;
     }
-    let root_mod_env = self.mod_env(self.database.modules.root().id());
-    let content = self.reg.render("cfg", &root_mod_env)?;
+    let links = self.link_envs()?;
+    let env = CfgFileEnv {
+      root: self.mod_env(self.database.modules.root().id())?,
+      reload_links: self.reload_link_envs(&links),
+      links,
+    };
+    let content = self.reg.render("cfg", &env)?;
     write(
       target
         .as_ref()
@@ -89,10 +288,94 @@ impl<'a> CSharp<'a> {
     if !common.exists() {
       create_dir(common.clone())?;
     }
-    let content = self.reg.render("util", &self.common_env)?;
+    let util = if self.common_env.unity {
+      "unity_util"
+    } else {
+      "util"
+    };
+    let content = self.reg.render(util, &self.common_env)?;
     write(common.join("Util.cs"), content)?;
     let content = self.reg.render("access", &self.common_env)?;
     write(common.join("IDataAccess.cs"), content)?;
+    if self.options.source_gen {
+      let content = self.reg.render("json_context", &self.json_context_env())?;
+      write(common.join("JsonContext.cs"), content)?;
+    }
+    let scriptable_object = self.common_env.unity && self.options.scriptable_object;
+    if scriptable_object {
+      // 编辑器脚本要放在 `Editor` 目录里, 不然打包时会报错
+      let editor = target.as_ref().join("Editor");
+      if !editor.exists() {
+        create_dir(&editor)?;
+      }
+      let env = EditorImporterFileEnv {
+        common_env: &self.common_env,
+        assets: self
+          .database
+          .tables()
+          .map(|t| self.asset_env(t.full_name()))
+          .collect(),
+      };
+      let content = self.reg.render("editor_importer", &env)?;
+      write(editor.join("CfgAssetImporter.cs"), content)?;
+    }
+
+    if self.options.alias_style == AliasStyle::Using {
+      let mut aliases: Vec<AliasEnv> = Vec::new();
+      for ty in self.database.types.iter() {
+        if let Type::Alias { full_name, target } = ty {
+          let name = self.named_type_name(full_name);
+          if aliases.iter().any(|a| a.name == name) {
+            return Err(别名重名(name).into());
+          }
+          aliases.push(AliasEnv {
+            common_env: &self.common_env,
+            namespace: String::new(),
+            name,
+            type_full_name: self.type_full_name_as(*target, AliasStyle::Inline),
+          });
+        }
+      }
+      let env = AliasesFileEnv {
+        common_env: &self.common_env,
+        aliases,
+      };
+      let content = self.reg.render("aliases", &env)?;
+      write(common.join("Aliases.cs"), content)?;
+    }
+
+    // 用到的定点数类型
+    let mut fixed_types = Vec::new();
+    for ty in self.database.types.iter() {
+      if let &Type::Fixed {
+        int_bits,
+        frac_bits,
+      } = ty
+        && !fixed_types.contains(&(int_bits, frac_bits))
+      {
+        fixed_types.push((int_bits, frac_bits));
+      }
+    }
+    for (int_bits, frac_bits) in fixed_types {
+      let wide = int_bits + frac_bits > 32;
+      // Unity 没有 `Int128`
+      let wide_mul_type = if self.common_env.unity {
+        "System.Numerics.BigInteger"
+      } else {
+        "Int128"
+      };
+      let env = FixedFileEnv {
+        common_env: &self.common_env,
+        name: self.fixed_type_name(int_bits, frac_bits),
+        int_bits,
+        frac_bits,
+        raw_type: if wide { "long" } else { "int" }.to_string(),
+        raw_type_name: if wide { "Int64" } else { "Int32" }.to_string(),
+        mul_type: if wide { wide_mul_type } else { "long" }.to_string(),
+      };
+      let content = self.reg.render("fixed", &env)?;
+      write(common.join(&env.name).with_added_extension("cs"), content)?;
+    }
 
     let root = target
       .as_ref()
@@ -100,7 +383,7 @@ impl<'a> CSharp<'a> {
     if !root.exists() {
       create_dir(root.clone())?;
     }
-    self.gen_mods(self.database.modules.root().id(), root);
+    self.gen_mods(self.database.modules.root().id(), root)?;
     Ok(())
   }
 
@@ -109,9 +392,10 @@ impl<'a> CSharp<'a> {
       create_dir(target.as_ref())?;
     }
     let mod_namespace = self.mod_namespace(mid);
+    self.check_namespace(mid, &mod_namespace)?;
 
     // 子模块
-    if let Some(env) = self.mod_env(mid) {
+    if let Some(env) = self.mod_env(mid)? {
       let mod_content = self.reg.render("mod", &env)?;
       write(
         target
@@ -126,69 +410,172 @@ impl<'a> CSharp<'a> {
       }
     }
 
+    if self.common_env.unity && self.options.scriptable_object {
+      for ch in self.database.modules.get(mid).unwrap().children() {
+        if let Some(did) = ch.value().data {
+          let data = self.database.get_data(did).unwrap();
+          let env = ScriptableObjectFileEnv {
+            common_env: &self.common_env,
+            namespace: mod_namespace.clone(),
+            type_full_name: self.table_type_full_name(&data.full_name, data.typ),
+            data_type_full_name: self.type_full_name(data.typ),
+            asset: self.asset_env(&data.full_name),
+          };
+          let content = self.reg.render("scriptable_object", &env)?;
+          write(
+            target
+              .as_ref()
+              .join(&env.asset.class_name)
+              .with_added_extension("cs"),
+            content,
+          )?;
+        }
+      }
+    }
+
+    let module = self.database.modules.get(mid).unwrap().value();
+    if let Some(did) = module.data
+      && self.options.table_class
+    {
+      let env = self.table_env(self.database.get_data(did).unwrap(), &mod_namespace)?;
+      let content = self.reg.render("table", &env)?;
+      write(
+        target.as_ref().join(&env.name).with_added_extension("cs"),
+        content,
+      )?;
+    }
+
     // 包含的类型
-    for (name, &id) in self
+    for &id in self
       .database
       .modules
       .get(mid)
       .unwrap()
       .value()
       .type_name_to_id
-      .iter()
+      .values()
     {
       let ty = self.database.get_type(id).unwrap();
-      match ty {
-        Type::Struct { full_name, fields } => {
-          let name = self.named_type_name(&full_name);
-
-          let mut field_envs = Vec::new();
-          for (fname, ftid) in fields.iter() {
-            let ftyname = self.type_full_name(*ftid);
-            let fenv = ClassFieldEnv {
-              name: fname.as_ref().clone(),
-              type_full_name: ftyname,
-            };
-            field_envs.push(fenv);
-          }
-          let env = ClassFileEnv {
-            common_env: &self.common_env,
-            namespace: mod_namespace.clone(),
-            name: name.clone(),
-            fields: field_envs,
-          };
+      if let Type::Struct {
+        full_name, fields, ..
+      } = ty
+      {
+        let name = self.named_type_name(full_name);
 
-          let content = self.reg.render("class", &env)?;
-          write(
-            target.as_ref().join(name).with_added_extension("cs"),
-            content,
-          )?;
+        let mut scope = Scope::new(full_name.as_str()).with_reserved(&name);
+        let mut field_envs = Vec::new();
+        for (fname, ftid) in fields.iter() {
+          let ftyname = self.type_full_name(*ftid);
+          let ident = scope.add(fname, self.options.naming.field)?;
+          let fenv = ClassFieldEnv {
+            // `@class` 在 json 里还是 `class`
+            json_name: (ident.trim_start_matches('@') != fname.as_ref())
+              .then(|| serde_json::Value::from(fname.as_ref()).to_string()),
+            name: ident,
+            type_full_name: ftyname,
+          };
+          field_envs.push(fenv);
         }
-        _ => {}
+        let mut ref_envs = Vec::new();
+        if let Some(table) = self.database.tables().find(|t| t.item_type() == id) {
+          for reference in self.database.references(table)? {
+            let item = self.database.table(&reference.target).unwrap().item_type();
+            let item = self.type_full_name(item);
+            ref_envs.push(RefFieldEnv {
+              name: scope.add(&reference.name, self.options.naming.field)?,
+              field_name: reference.field.to_string(),
+              type_full_name: match reference.shape {
+                RefShape::One => item,
+                RefShape::Optional => item + "?",
+                RefShape::List => format!("IReadOnlyList<{item}>"),
+              },
+              attribute: if self.common_env.unity && !self.common_env.newtonsoft {
+                "NonSerialized"
+              } else {
+                "JsonIgnore"
+              }
+              .to_string(),
+            });
+          }
+        }
+        let env = ClassFileEnv {
+          common_env: &self.common_env,
+          namespace: mod_namespace.clone(),
+          name: name.clone(),
+          fields: field_envs,
+          refs: ref_envs,
+        };
+
+        let content = self.reg.render("class", &env)?;
+        write(
+          target.as_ref().join(name).with_added_extension("cs"),
+          content,
+        )?;
+      } else if let Type::Alias {
+        full_name,
+        target: alias_target,
+      } = ty
+        && self.options.alias_style == AliasStyle::Struct
+      {
+        let name = self.named_type_name(full_name);
+        let env = AliasEnv {
+          common_env: &self.common_env,
+          namespace: mod_namespace.clone(),
+          name: name.clone(),
+          type_full_name: self.type_full_name(*alias_target),
+        };
+        let content = self.reg.render("alias", &env)?;
+        write(
+          target.as_ref().join(name).with_added_extension("cs"),
+          content,
+        )?;
       }
     }
     Ok(())
   }
 
-  fn mod_env(&self, mid: NodeId) -> Option<ModuleFileEnv> {
+  fn mod_env(&self, mid: NodeId) -> Result<Option<ModuleFileEnv<'_>>> {
     let mut has_chlid = false;
     let mut data = Vec::new();
     let mut mods = Vec::new();
+    let mod_namespace = self.mod_namespace(mid);
+    let mut scope = Scope::new(format!(
+      "{mod_namespace}.{}",
+      self.common_env.mod_class_name
+    ))
+    .with_reserved(&self.common_env.mod_class_name);
+    if self.options.lazy {
+      scope = scope
+        .with_reserved("Preload")
+        .with_reserved("CollectPreload");
+    }
+    if self.options.hot_reload {
+      scope = scope.with_reserved("ReloadTable");
+      if mid == self.database.modules.root().id() {
+        scope = scope.with_reserved("Reload").with_reserved("OnReloaded");
+      }
+    }
     for ch in self.database.modules.get(mid).unwrap().children() {
       has_chlid = true;
       let name = ch.value().name.clone();
-      dbg!(&name);
+      let ident = scope.add(&name, self.options.naming.field)?;
       if let Some(did) = ch.value().data {
-        let tid = self.database.get_data(did).unwrap().typ;
-        let type_full_name = self.type_full_name(tid);
+        let table = self.database.get_data(did).unwrap();
         let fenv = DataFieldEnv {
-          name: name.clone(),
-          type_full_name,
+          name: ident,
+          type_full_name: self.table_type_full_name(&table.full_name, table.typ),
+          data_type_full_name: self.type_full_name(table.typ),
           data_file_name: name.clone() + ".json",
+          table_name: name.clone(),
+          linked: !self
+            .database
+            .references(self.database.table(&table.full_name).unwrap())?
+            .is_empty(),
         };
         data.push(fenv);
       } else {
         let fenv = SubmoduleFieldEnv {
-          name: name.clone(),
+          name: ident,
           namespace: self.mod_namespace(ch.id()),
           data_folder_name: name.clone(),
         };
@@ -196,20 +583,461 @@ impl<'a> CSharp<'a> {
       }
     }
     if has_chlid {
-      let mod_namespace = self.mod_namespace(mid);
-      let mut env = ModuleFileEnv {
+      let env = ModuleFileEnv {
         common_env: &self.common_env,
-        mod_namespace: mod_namespace.clone(),
+        mod_namespace,
         data_fields: data,
         submodule_fields: mods,
       };
-      Some(env)
+      Ok(Some(env))
     } else {
-      None
+      Ok(None)
+    }
+  }
+
+  /// 一个命名空间里的 `Mod`, 类, 和子命名空间不能重名
+  fn check_namespace(&self, mid: NodeId, mod_namespace: &str) -> Result<()> {
+    let module = self.database.modules.get(mid).unwrap();
+    let mut scope = Scope::new(mod_namespace);
+    if module.has_children() {
+      scope = scope.with_reserved(&self.common_env.mod_class_name);
+    }
+    for ch in module.children() {
+      scope.add(&ch.value().name, self.options.naming.namespace)?;
+    }
+    if let Some(did) = module.value().data
+      && self.options.table_class
+    {
+      scope.add(
+        &table_class_name(&self.database.get_data(did).unwrap().full_name),
+        self.options.naming.class,
+      )?;
+    }
+    if self.common_env.unity && self.options.scriptable_object {
+      for ch in module.children().filter(|ch| ch.value().data.is_some()) {
+        scope.add(
+          &(ch.value().name.clone() + "Asset"),
+          self.options.naming.class,
+        )?;
+      }
+    }
+    for &id in module.value().type_name_to_id.values() {
+      let full_name = match self.database.get_type(id).unwrap() {
+        Type::Struct { full_name, .. } => full_name,
+        Type::Alias { full_name, .. } if self.options.alias_style == AliasStyle::Struct => {
+          full_name
+        }
+        _ => continue,
+      };
+      scope.add(config::path_name(full_name), self.options.naming.class)?;
+    }
+    Ok(())
+  }
+
+  /// Unity 的 C# 版本和 json 库不支持的写法, 生成前就报错
+  fn check_unity(&self) -> Result<()> {
+    let unsupported = |what: &str| Err(Unity不支持(what.to_string()).into());
+    if self.options.source_gen {
+      return unsupported("source_gen");
+    }
+    if self.options.mutability == Mutability::Immutable {
+      return unsupported("immutable, 没有 System.Collections.Immutable");
+    }
+    if self.options.alias_style != AliasStyle::Inline {
+      return unsupported("using 和 struct 写法的别名, 需要 C# 10 以上");
+    }
+    // SerializableDictionary 只能从键值对的列表读
+    if self.database.options.dict_layout != DictLayout::Entries {
+      return unsupported("object 写法的字典, 要用 --dict-layout entries");
+    }
+    if self.options.unity_json != UnityJson::JsonUtility {
+      return Ok(());
+    }
+    if self.options.mutability != Mutability::Mutable {
+      return Err(JsonUtility不支持("属性".to_string(), "read_only".to_string()).into());
+    }
+    let db = self.database;
+    let is_value_type = |tid| {
+      matches!(
+        db.get_type(db.resolve_alias(tid)).unwrap(),
+        Type::Int | Type::Float | Type::Bool | Type::Fixed { .. } | Type::Tuple { .. }
+      )
+    };
+    // 内置类型总是在, 只查表里用到的
+    let mut used = db.tables().map(|t| t.data().typ).collect::<Vec<_>>();
+    let mut i = 0;
+    while let Some(&tid) = used.get(i) {
+      i += 1;
+      let children = match db.get_type(tid).unwrap() {
+        &Type::Optional(inner) | &Type::List(inner) | &Type::Alias { target: inner, .. } => {
+          vec![inner]
+        }
+        &Type::Dict(key, value) => vec![key, value],
+        Type::Tuple { items, .. } => items.clone(),
+        Type::Struct { fields, .. } => fields.values().copied().collect(),
+        _ => vec![],
+      };
+      for child in children {
+        if !used.contains(&child) {
+          used.push(child);
+        }
+      }
+    }
+    for tid in used {
+      let what = match db.get_type(tid).unwrap() {
+        Type::Tuple { .. } => "元组",
+        Type::DateTime | Type::Date | Type::Duration => "时间",
+        Type::Fixed { .. } => "定点数",
+        &Type::Optional(inner) if is_value_type(inner) => "可空的值类型",
+        &Type::List(item)
+          if matches!(db.get_type(db.resolve_alias(item)).unwrap(), Type::List(_)) =>
+        {
+          "嵌套列表"
+        }
+        Type::Struct { fields, .. }
+          if fields.keys().any(|f| {
+            naming::identifier(f, self.options.naming.field).trim_start_matches('@') != &**f
+          }) =>
+        {
+          "和字段名不一样的 json 名"
+        }
+        _ => continue,
+      };
+      return Err(JsonUtility不支持(what.to_string(), db.type_full_name(tid)).into());
+    }
+    Ok(())
+  }
+
+  /// 有引用的表, 给 `Cfg.Link` 用
+  fn link_envs(&self) -> Result<Vec<LinkEnv>> {
+    let mut links = Vec::new();
+    for table in self.database.tables() {
+      let refs = self
+        .database
+        .references(table)?
+        .into_iter()
+        .map(|reference| {
+          let target = self.database.table(&reference.target).unwrap();
+          LinkRefEnv {
+            field: naming::identifier(&reference.field, self.options.naming.field),
+            field_name: reference.field.to_string(),
+            name: naming::identifier(&reference.name, self.options.naming.field),
+            target_access: self.link_access(&reference.target),
+            item_type: self.type_full_name(target.item_type()),
+            optional: reference.shape == RefShape::Optional,
+            list: reference.shape == RefShape::List,
+            target_full_name: reference.target,
+          }
+        })
+        .collect::<Vec<_>>();
+      if !refs.is_empty() {
+        let mut targets = Vec::new();
+        for r in refs.iter() {
+          let target = self.table_access(&r.target_full_name);
+          if !targets.contains(&target) {
+            targets.push(target);
+          }
+        }
+        links.push(LinkEnv {
+          table_full_name: table.full_name().to_string(),
+          access: self.link_access(table.full_name()),
+          module_access: self.table_access(config::path_parent(table.full_name())),
+          data_index: self.data_index(table.full_name()),
+          targets,
+          refs,
+        });
+      }
+    }
+    Ok(links)
+  }
+
+  /// 表在所在模块的数据表里排第几
+  fn data_index(&self, table_full_name: &str) -> usize {
+    let module = self
+      .database
+      .get_module(config::path_parent(table_full_name))
+      .unwrap();
+    self
+      .database
+      .modules
+      .get(module)
+      .unwrap()
+      .children()
+      .filter_map(|ch| ch.value().data)
+      .position(|did| self.database.data[did].full_name == table_full_name)
+      .unwrap()
+  }
+
+  /// `Cfg.Link` 里读表的写法. 懒加载时直接取 `_lazy序号`, 不会连带去对别的表, 每张表只报自己的错
+  fn link_access(&self, table_full_name: &str) -> String {
+    if !self.options.lazy {
+      return self.table_access(table_full_name);
+    }
+    format!(
+      "{}._lazy{}.Value",
+      self.table_access(config::path_parent(table_full_name)),
+      self.data_index(table_full_name)
+    )
+  }
+
+  /// `Cfg.ReloadTable` 只改新读的行: 直接或间接引用了这张表的表也重新读, 再对上这些表的引用
+  fn reload_link_envs(&self, links: &[LinkEnv]) -> Vec<ReloadLinkEnv> {
+    let relative = |full_name: &str| full_name[config::PATH_SPLITOR.len_utf8()..].to_string();
+    self
+      .database
+      .tables()
+      .filter_map(|table| {
+        let mut reload = vec![table.full_name().to_string()];
+        let mut i = 0;
+        while let Some(target) = reload.get(i).cloned() {
+          i += 1;
+          for link in links {
+            if link.refs.iter().any(|r| r.target_full_name == target)
+              && !reload.contains(&link.table_full_name)
+            {
+              reload.push(link.table_full_name.clone());
+            }
+          }
+        }
+        let link_indexes = links
+          .iter()
+          .enumerate()
+          .filter(|(_, link)| reload.contains(&link.table_full_name))
+          .map(|(i, _)| i)
+          .collect::<Vec<_>>();
+        (!link_indexes.is_empty()).then(|| ReloadLinkEnv {
+          name: relative(table.full_name()),
+          referrers: reload[1..].iter().map(|name| relative(name)).collect(),
+          links: link_indexes,
+        })
+      })
+      .collect()
+  }
+
+  /// 在 `Cfg.Link` 里怎么拿到这张表, 例: `mod.命名空间.a`
+  fn table_access(&self, table_full_name: &str) -> String {
+    std::iter::once("mod".to_string())
+      .chain(
+        config::path_components(table_full_name)
+          .into_iter()
+          .skip(1)
+          .map(|s| naming::identifier(s, self.options.naming.field)),
+      )
+      .collect::<Vec<_>>()
+      .join(".")
+  }
+
+  /// 表类和行类型在表自己的命名空间里, 例: `Types.掉落.掉落_table`
+  fn table_type_full_name(&self, table_full_name: &str, tid: usize) -> String {
+    if !self.options.table_class {
+      return self.type_full_name(tid);
+    }
+    [
+      self.namespace(table_full_name),
+      naming::identifier(
+        &table_class_name(table_full_name),
+        self.options.naming.class,
+      ),
+    ]
+    .join(NAMESPACE_SEPARATOR)
+  }
+
+  /// 索引的方法名和 `Get` 之类的固定方法不能重名
+  fn table_env(&self, data: &Data, namespace: &str) -> Result<TableFileEnv<'_>> {
+    let Some(&Type::Dict(key, item)) = self.database.get_type(data.typ) else {
+      unreachable!("表的类型总是字典")
+    };
+    let Some(Type::Struct { fields, .. }) = self.database.get_type(item) else {
+      unreachable!("表的行类型总是结构体")
+    };
+    let name = naming::identifier(
+      &table_class_name(&data.full_name),
+      self.options.naming.class,
+    );
+    let mut scope = Scope::new(format!("{namespace}.{name}")).with_reserved(&name);
+    for reserved in [
+      "Get",
+      "TryGet",
+      "All",
+      "Count",
+      "Keys",
+      "Values",
+      "ContainsKey",
+      "TryGetValue",
+      "GetEnumerator",
+    ] {
+      scope = scope.with_reserved(reserved);
+    }
+    let mut indexes = Vec::new();
+    for index in self.database.indexes(data.typ) {
+      let mut tid = self.database.resolve_alias(fields[&index.field]);
+      let optional = if let &Type::Optional(inner) = self.database.get_type(tid).unwrap() {
+        tid = inner;
+        true
+      } else {
+        false
+      };
+      // 列表, 字典这些在 C# 里按引用比较, 不能当键
+      let hashable = matches!(
+        self
+          .database
+          .get_type(self.database.resolve_alias(tid))
+          .unwrap(),
+        Type::Int
+          | Type::Float
+          | Type::String
+          | Type::Bool
+          | Type::DateTime
+          | Type::Date
+          | Type::Duration
+          | Type::Fixed { .. }
+      );
+      if !hashable {
+        return Err(
+          索引列的类型不支持(
+            data.full_name.clone(),
+            index.field.to_string(),
+            self.database.type_full_name(tid),
+          )
+          .into(),
+        );
+      }
+      let prefix = if index.unique { "by_" } else { "group_by_" };
+      let method = scope.add(&format!("{prefix}{}", index.field), Casing::Pascal)?;
+      indexes.push(IndexEnv {
+        field: naming::identifier(&index.field, self.options.naming.field),
+        field_name: index.field.to_string(),
+        key_type: self.type_full_name(tid),
+        optional,
+        unique: index.unique,
+        try_method: index.unique.then(|| format!("Try{method}")),
+        method,
+      });
+    }
+    Ok(TableFileEnv {
+      common_env: &self.common_env,
+      namespace: self.namespace(&data.full_name),
+      name,
+      table_full_name: data.full_name.clone(),
+      key_type: self.type_full_name(key),
+      item_type: self.type_full_name(item),
+      data_type: self.type_full_name(data.typ),
+      indexes,
+    })
+  }
+
+  /// 表对应的 `ScriptableObject`, 和表的模块放在同一个命名空间
+  fn asset_env(&self, table_full_name: &str) -> AssetEnv {
+    let class_name = naming::identifier(
+      &(config::path_name(table_full_name).to_string() + "Asset"),
+      self.options.naming.class,
+    );
+    let path = config::path_components(table_full_name)[1..].join("/");
+    AssetEnv {
+      class_full_name: [
+        self.namespace(config::path_parent(table_full_name)),
+        class_name.clone(),
+      ]
+      .join(NAMESPACE_SEPARATOR),
+      class_name,
+      data_path: path.clone() + ".json",
+      asset_path: path + ".asset",
+    }
+  }
+
+  /// 每种字典和元组都有自己的转换器. 表的类型和转换器里用到的类型都要 `[JsonSerializable]`
+  fn json_context_env(&self) -> JsonContextEnv<'_> {
+    fn add(types: &mut Vec<String>, name: String) {
+      if !types.contains(&name) {
+        types.push(name);
+      }
+    }
+    let mut serializable_types = Vec::new();
+    for table in self.database.tables() {
+      add(
+        &mut serializable_types,
+        self.runtime_type_name(table.data().typ),
+      );
+    }
+    let mut dictionaries: Vec<DictionaryConverterEnv> = Vec::new();
+    let mut tuples: Vec<TupleConverterEnv> = Vec::new();
+    for (tid, ty) in self.database.types.iter().enumerate() {
+      match ty {
+        &Type::Dict(key, value) => {
+          let type_name = self.runtime_type_name(tid);
+          if dictionaries.iter().any(|d| d.type_name == type_name) {
+            continue;
+          }
+          let (key, value) = (self.runtime_type_name(key), self.runtime_type_name(value));
+          add(&mut serializable_types, key.clone());
+          add(&mut serializable_types, value.clone());
+          dictionaries.push(DictionaryConverterEnv {
+            name: format!("DictionaryConverter{}", dictionaries.len()),
+            type_name,
+            key,
+            value,
+            wrap: match self.options.mutability {
+              Mutability::Immutable => "dictionary.ToImmutableDictionary()",
+              _ => "dictionary",
+            }
+            .to_string(),
+          });
+        }
+        Type::Tuple { items, .. } => {
+          let type_name = self.runtime_type_name(tid);
+          if tuples.iter().any(|t| t.type_name == type_name) {
+            continue;
+          }
+          let items = items
+            .iter()
+            .enumerate()
+            .map(|(i, &item)| TupleItemEnv {
+              var: format!("item{i}"),
+              field: format!("Item{}", i + 1),
+              type_name: self.runtime_type_name(item),
+            })
+            .collect::<Vec<_>>();
+          for item in &items {
+            add(&mut serializable_types, item.type_name.clone());
+          }
+          tuples.push(TupleConverterEnv {
+            name: format!("TupleConverter{}", tuples.len()),
+            type_name,
+            args: items
+              .iter()
+              .map(|i| i.var.as_str())
+              .collect::<Vec<_>>()
+              .join(", "),
+            items,
+          });
+        }
+        _ => {}
+      }
+    }
+    JsonContextEnv {
+      common_env: &self.common_env,
+      serializable_types,
+      dictionaries,
+      tuples,
     }
   }
 
   fn type_full_name(&self, tid: usize) -> String {
+    self.type_full_name_as(tid, self.options.alias_style)
+  }
+
+  /// `using` 别名里不能再引用别的 `using` 别名, 所以生成别名本身时要展开
+  fn type_full_name_as(&self, tid: usize, alias_style: AliasStyle) -> String {
+    self.type_name(tid, alias_style, true)
+  }
+
+  /// `typeof` 里元组的元素不能有名字
+  fn runtime_type_name(&self, tid: usize) -> String {
+    self.type_name(tid, self.options.alias_style, false)
+  }
+
+  fn type_name(&self, tid: usize, alias_style: AliasStyle, tuple_names: bool) -> String {
+    let name = |tid: usize| self.type_name(tid, alias_style, tuple_names);
     match self.database.get_type(tid).unwrap() {
       Type::Unknown => todo!(),
       Type::Placeholder(_) => todo!(),
@@ -218,39 +1046,114 @@ impl<'a> CSharp<'a> {
       Type::Float => "float".to_string(),
       Type::String => "string".to_string(),
       Type::Bool => "bool".to_string(),
-      Type::List(pid) => self.type_full_name(*pid) + "[]",
+      Type::DateTime => "DateTimeOffset".to_string(),
+      Type::Date if self.common_env.unity => "DateTime".to_string(),
+      Type::Date => "DateOnly".to_string(),
+      Type::Duration => "TimeSpan".to_string(),
+      &Type::Fixed {
+        int_bits,
+        frac_bits,
+      } => [
+        self.common_env.common_namespace.as_str(),
+        &self.fixed_type_name(int_bits, frac_bits),
+      ]
+      .join(NAMESPACE_SEPARATOR),
+      Type::Optional(pid) => name(*pid) + "?",
+      Type::List(pid) => {
+        let item = name(*pid);
+        match self.options.mutability {
+          Mutability::Mutable => item + "[]",
+          Mutability::ReadOnly => format!("IReadOnlyList<{item}>"),
+          Mutability::Immutable => format!("System.Collections.Immutable.ImmutableArray<{item}>"),
+        }
+      }
+      // 字典在 json 里是 `entries` 列表, 见 [config::DictLayout::Entries]
+      Type::Dict(pid1, pid2) if self.common_env.unity => format!(
+        "{}.SerializableDictionary<{}, {}>",
+        self.common_env.common_namespace,
+        name(*pid1),
+        name(*pid2)
+      ),
       Type::Dict(pid1, pid2) => {
-        format!(
-          "Dictionary<{}, {}>",
-          self.type_full_name(*pid1),
-          self.type_full_name(*pid2)
-        )
+        let dict = match self.options.mutability {
+          Mutability::Mutable => "Dictionary",
+          Mutability::ReadOnly => "IReadOnlyDictionary",
+          Mutability::Immutable => "System.Collections.Immutable.ImmutableDictionary",
+        };
+        format!("{dict}<{}, {}>", name(*pid1), name(*pid2))
+      }
+      // 一个元素的元组没有 `(T name)` 这种写法
+      Type::Tuple { items, .. } if items.len() == 1 => {
+        format!("ValueTuple<{}>", name(items[0]))
+      }
+      Type::Tuple { items, names } => {
+        let items = items
+          .iter()
+          .zip(names.iter())
+          .map(|(tid, item_name)| match item_name {
+            Some(item_name) if tuple_names => format!(
+              "{} {}",
+              name(*tid),
+              naming::identifier(item_name, self.options.naming.field)
+            ),
+            _ => name(*tid),
+          })
+          .collect::<Vec<_>>();
+        format!("({})", items.join(", "))
       }
-      Type::Struct { full_name, fields } => self.named_type_full_name(&full_name),
+      Type::Struct { full_name, .. } => self.named_type_full_name(full_name),
+      Type::Alias { full_name, target } => match alias_style {
+        AliasStyle::Inline => name(*target),
+        AliasStyle::Using => self.named_type_name(full_name),
+        AliasStyle::Struct => self.named_type_full_name(full_name),
+      },
     }
   }
 
   fn mod_namespace(&self, mid: NodeId) -> String {
-    let nm =
-      self.common_env.data_type_namespace_name.clone() + &self.database.module_full_name(mid);
+    self.namespace(&self.database.module_full_name(mid))
+  }
+
+  /// 模块路径的每一段都要处理成标识符
+  fn namespace(&self, module_full_name: &str) -> String {
+    let segments = config::path_components(module_full_name)
+      .into_iter()
+      .skip(1)
+      .map(|s| naming::identifier(s, self.options.naming.namespace));
+    let nm = std::iter::once(self.common_env.data_type_namespace_name.clone())
+      .chain(segments)
+      .collect::<Vec<_>>()
+      .join(NAMESPACE_SEPARATOR);
     self.full_name(&nm)
   }
 
   fn named_type_full_name(&self, engine_full_name: &str) -> String {
-    let nm = self.common_env.data_type_namespace_name.clone() + engine_full_name;
-    self.full_name(&nm)
+    [
+      self.namespace(config::path_parent(engine_full_name)),
+      self.named_type_name(engine_full_name),
+    ]
+    .join(NAMESPACE_SEPARATOR)
   }
 
   fn full_name(&self, rel_proj_name: &str) -> String {
     if let Some(pns) = &self.common_env.project_namespace {
-      return [&**pns, rel_proj_name].join(NAMESPACE_SEPARATOR);
+      [&**pns, rel_proj_name].join(NAMESPACE_SEPARATOR)
     } else {
-      return rel_proj_name.to_string();
+      rel_proj_name.to_string()
     }
   }
 
   fn named_type_name(&self, engine_full_name: &str) -> String {
-    config::path_name(engine_full_name).to_string()
+    naming::identifier(
+      config::path_name(engine_full_name),
+      self.options.naming.class,
+    )
+  }
+
+  /// 例: `Fx16_16`
+  fn fixed_type_name(&self, int_bits: u8, frac_bits: u8) -> String {
+    let name = config::fixed_type_name(int_bits, frac_bits);
+    name[..1].to_uppercase() + &name[1..]
   }
 }
 
@@ -265,6 +1168,24 @@ pub struct CommonEnv {
   pub common_namespace: String,
   data_type_namespace: String,
   cfg_class_name: String,
+  /// 类型是 `record`, 属性不能改
+  pub immutable: bool,
+  /// 见 [CSharpOptions::source_gen]
+  pub source_gen: bool,
+  /// 见 [CSharpOptions::lazy]
+  pub lazy: bool,
+  /// 见 [CSharpOptions::async_load]
+  pub async_load: bool,
+  /// 见 [CSharpOptions::table_class]
+  pub table_class: bool,
+  /// 见 [CSharpOptions::hot_reload]
+  pub hot_reload: bool,
+  /// `namespace X { ... }`, 不用 C# 10 的 `namespace X;`
+  pub block_namespace: bool,
+  /// 见 [Profile::Unity]
+  pub unity: bool,
+  /// Unity 下用 Newtonsoft.Json, 否则是 JsonUtility
+  pub newtonsoft: bool,
 }
 #[derive(Debug, Serialize)]
 pub struct ModuleFileEnv<'a> {
@@ -278,8 +1199,15 @@ pub struct ModuleFileEnv<'a> {
 #[derive(Debug, Serialize)]
 pub struct DataFieldEnv {
   pub name: String,
+  /// 有表类时是表类, 否则和 `data_type_full_name` 一样
   pub type_full_name: String,
+  /// json 反序列化成的字典
+  pub data_type_full_name: String,
   pub data_file_name: String,
+  /// 表在模块里的名字, `ReloadTable` 按这个找
+  pub table_name: String,
+  /// 有引用列, 懒加载时第一次用到要先对上引用
+  pub linked: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -296,13 +1224,796 @@ pub struct ClassFileEnv<'a> {
   pub namespace: String,
   pub name: String,
   pub fields: Vec<ClassFieldEnv>,
+  /// 只有表的行类型有
+  pub refs: Vec<RefFieldEnv>,
+}
+
+/// 引用列指向的行, 不进 json
+#[derive(Debug, Serialize)]
+pub struct RefFieldEnv {
+  pub name: String,
+  pub field_name: String,
+  pub type_full_name: String,
+  /// 不序列化的标注, 例: `JsonIgnore`
+  pub attribute: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CfgFileEnv<'a> {
+  /// 只有根模块是空的时候是 [None]
+  #[serde(flatten)]
+  pub root: Option<ModuleFileEnv<'a>>,
+  pub links: Vec<LinkEnv>,
+  pub reload_links: Vec<ReloadLinkEnv>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReloadLinkEnv {
+  /// 去掉开头 `.` 的全名, 和 `Mod.ReloadTable` 的参数一样
+  pub name: String,
+  /// 要一起重新读的表
+  pub referrers: Vec<String>,
+  /// 要重新对的表在 `links` 里的序号
+  pub links: Vec<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LinkEnv {
+  pub table_full_name: String,
+  pub access: String,
+  /// 表所在的模块, 例: `mod.命名空间`
+  pub module_access: String,
+  /// 表在模块的数据表里排第几, 对应 `Mod` 里的 `_link序号`
+  pub data_index: usize,
+  /// 指向的表, 懒加载时对完这张表还要对上它们, 例: `mod.物品`
+  pub targets: Vec<String>,
+  pub refs: Vec<LinkRefEnv>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LinkRefEnv {
+  /// 存主键的字段
+  pub field: String,
+  pub field_name: String,
+  /// 存指向的行的字段
+  pub name: String,
+  pub target_full_name: String,
+  pub target_access: String,
+  /// 指向的表的行类型
+  pub item_type: String,
+  pub optional: bool,
+  pub list: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FixedFileEnv<'a> {
+  #[serde(flatten)]
+  pub common_env: &'a CommonEnv,
+  pub name: String,
+  pub int_bits: u8,
+  pub frac_bits: u8,
+  /// 存储用的整数类型
+  pub raw_type: String,
+  /// `Utf8JsonReader.GetXXX` 用的类型名
+  pub raw_type_name: String,
+  /// 乘除法的中间结果类型
+  pub mul_type: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AliasEnv<'a> {
+  #[serde(flatten)]
+  pub common_env: &'a CommonEnv,
+  pub namespace: String,
+  pub name: String,
+  pub type_full_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AliasesFileEnv<'a> {
+  #[serde(flatten)]
+  pub common_env: &'a CommonEnv,
+  pub aliases: Vec<AliasEnv<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonContextEnv<'a> {
+  #[serde(flatten)]
+  pub common_env: &'a CommonEnv,
+  pub serializable_types: Vec<String>,
+  pub dictionaries: Vec<DictionaryConverterEnv>,
+  pub tuples: Vec<TupleConverterEnv>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DictionaryConverterEnv {
+  pub name: String,
+  pub type_name: String,
+  pub key: String,
+  pub value: String,
+  /// 读出来的 `Dictionary` 转成 `type_name` 的表达式
+  pub wrap: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TupleConverterEnv {
+  pub name: String,
+  pub type_name: String,
+  pub items: Vec<TupleItemEnv>,
+  /// `ValueTuple.Create` 的参数
+  pub args: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TupleItemEnv {
+  pub var: String,
+  pub field: String,
+  pub type_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableFileEnv<'a> {
+  #[serde(flatten)]
+  pub common_env: &'a CommonEnv,
+  pub namespace: String,
+  pub name: String,
+  pub table_full_name: String,
+  pub key_type: String,
+  pub item_type: String,
+  /// 见 [DataFieldEnv::data_type_full_name]
+  pub data_type: String,
+  pub indexes: Vec<IndexEnv>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndexEnv {
+  /// 行类型里的字段
+  pub field: String,
+  /// 表里写的列名
+  pub field_name: String,
+  /// 可空的列去掉 `?`, 没填的行不进索引
+  pub key_type: String,
+  pub optional: bool,
+  pub unique: bool,
+  /// `ByXxx` 或 `GroupByXxx`
+  pub method: String,
+  /// 只有 `unique` 的有
+  pub try_method: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AssetEnv {
+  pub class_name: String,
+  pub class_full_name: String,
+  /// 相对数据目录, 用 `/` 分隔
+  pub data_path: String,
+  /// 相对资源目录, 用 `/` 分隔
+  pub asset_path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScriptableObjectFileEnv<'a> {
+  #[serde(flatten)]
+  pub common_env: &'a CommonEnv,
+  pub namespace: String,
+  /// 见 [DataFieldEnv]
+  pub type_full_name: String,
+  pub data_type_full_name: String,
+  pub asset: AssetEnv,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EditorImporterFileEnv<'a> {
+  #[serde(flatten)]
+  pub common_env: &'a CommonEnv,
+  pub assets: Vec<AssetEnv>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct ClassFieldEnv {
   pub name: String,
+  /// 和 `name` 不一样时才有, 已经带引号
+  pub json_name: Option<String>,
   pub type_full_name: String,
 }
 
-const NAMESPACE_SEPARATOR: &'static str = ".";
-const PROJECT_NAMESPACE: &'static str = "project_namespace";
+const NAMESPACE_SEPARATOR: &str = ".";
+
+/// 还没处理成标识符
+fn table_class_name(table_full_name: &str) -> String {
+  config::path_name(table_full_name).to_string() + "_table"
+}
+
+pub mod error {
+  use thiserror::Error;
+
+  #[derive(Debug, Error)]
+  pub enum Error {
+    #[error("别名重名: {0}, `using` 写法下别名不能重名")]
+    别名重名(String),
+    #[error("{0} 里 `{1}` 和 `{2}` 的标识符都是 `{3}`")]
+    标识符冲突(String, String, String, String),
+    #[error("{0} 的 {1} 列是 {2}, 不能建索引")]
+    索引列的类型不支持(String, String, String),
+    #[error("Unity 下不支持 {0}")]
+    Unity不支持(String),
+    #[error("JsonUtility 不支持{0}: {1}")]
+    JsonUtility不支持(String, String),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::{
+    fs::{read_to_string, remove_dir_all},
+    path::PathBuf,
+  };
+
+  use anyhow::Result;
+
+  use crate::basic::{
+    code_generator::csharp::{AliasStyle, CSharp, CSharpOptions, Mutability, Profile, UnityJson},
+    config::{DictLayout, Options},
+    database::Database,
+    raw_table::RawTable,
+  };
+
+  fn load_test_proj() -> Result<Database> {
+    let mut db = Database::new();
+    db.load_project("./test/proj/")?;
+    Ok(db)
+  }
+
+  /// `.商店.货架` 的三列引用 `.物品`
+  fn references_db() -> Result<Database> {
+    let mut db = Database::new();
+    db.add_tables(vec![
+      // 升级指向自己的表, 两行互相引用
+      RawTable::from_grid(
+        ".物品",
+        &[
+          vec!["", "id", "升级"],
+          vec!["", "i", "i?"],
+          vec!["标注", "", "ref=.物品"],
+          vec!["", "1", "2"],
+          vec!["", "2", "1"],
+        ],
+      )?,
+      RawTable::from_grid(
+        ".商店.货架",
+        &[
+          vec!["", "id", "物品", "赠品", "组合"],
+          vec!["", "i", "i", "i?", "l<i>"],
+          vec!["标注", "", "ref=物品, ref_as=item", "ref=物品", "ref=物品"],
+          vec!["", "1", "1", "", "1"],
+        ],
+      )?,
+    ])?;
+    Ok(db)
+  }
+
+  /// 生成到临时目录里的 `csharp_test_名字`, 先清空, 不留上次的文件
+  fn generate(db: &Database, name: &str, options: CSharpOptions) -> Result<PathBuf> {
+    let out = std::env::temp_dir().join(format!("csharp_test_{name}"));
+    let _ = remove_dir_all(&out);
+    CSharp::try_new(db, options)?.generate(&out)?;
+    Ok(out)
+  }
+
+  /// 在 `dir` 里放一个项目, 编译生成的代码和 `dir` 里别的 `.cs`, 再执行 `dotnet 参数`. 警告不算错
+  #[cfg(feature = "dotnet")]
+  fn dotnet(dir: &std::path::Path, output_type: &str, args: &[&str]) -> Result<()> {
+    std::fs::write(
+      dir.join("Gen.csproj"),
+      format!(
+        r#"<Project Sdk="Microsoft.NET.Sdk">
+  <PropertyGroup>
+    <OutputType>{output_type}</OutputType>
+    <TargetFramework>net8.0</TargetFramework>
+    <Nullable>enable</Nullable>
+    <ImplicitUsings>disable</ImplicitUsings>
+  </PropertyGroup>
+</Project>
+"#
+      ),
+    )?;
+    let output = std::process::Command::new("dotnet")
+      .args(args)
+      .current_dir(dir)
+      .output()?;
+    anyhow::ensure!(
+      output.status.success(),
+      "{} 里 dotnet {} 失败:\n{}{}",
+      dir.display(),
+      args.join(" "),
+      String::from_utf8_lossy(&output.stdout),
+      String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(())
+  }
+
+  /// `cargo test --features dotnet`
+  #[cfg(feature = "dotnet")]
+  #[test]
+  fn test_dotnet_build() -> Result<()> {
+    let db = load_test_proj()?;
+    let all = CSharpOptions {
+      lazy: true,
+      async_load: true,
+      table_class: true,
+      hot_reload: true,
+      ..CSharpOptions::default()
+    };
+    for (name, options) in [
+      ("dotnet_default", CSharpOptions::default()),
+      ("dotnet_all", all.clone()),
+      (
+        "dotnet_read_only",
+        CSharpOptions {
+          mutability: Mutability::ReadOnly,
+          alias_style: AliasStyle::Using,
+          ..all.clone()
+        },
+      ),
+      (
+        "dotnet_immutable",
+        CSharpOptions {
+          mutability: Mutability::Immutable,
+          alias_style: AliasStyle::Struct,
+          source_gen: true,
+          ..all
+        },
+      ),
+    ] {
+      let build = ["build", "-nologo", "-v", "q"];
+      dotnet(&generate(&db, name, options.clone())?, "Library", &build)?;
+      let name = format!("{name}_references");
+      dotnet(
+        &generate(&references_db()?, &name, options)?,
+        "Library",
+        &build,
+      )?;
+    }
+    Ok(())
+  }
+
+  /// 运行时测试共用的开头, 从第一个参数的目录读 json
+  #[cfg(feature = "dotnet")]
+  const DATA_ACCESS: &str = r#"using System;
+using System.Collections.Generic;
+using System.IO;
+using __Gen.Cfg;
+using __Gen.Cfg.Common;
+
+class DataPath : IDataPath {
+    public readonly string Path;
+    public DataPath(string path) { Path = path; }
+}
+
+class DataAccess : IDataAccess {
+    readonly string root;
+    public DataAccess(string root) { this.root = root; }
+    public IDataPath RootPath() => new DataPath(root);
+    public IDataPath JoinPath(IDataPath path, string item) => new DataPath(Path.Join(((DataPath)path).Path, item));
+    public string GetData(IDataPath path) => File.ReadAllText(((DataPath)path).Path);
+}
+
+"#;
+
+  /// 生成代码和 json, 接上 `program` 当 `Program.cs` 运行, 返回值不是 0 时失败
+  #[cfg(feature = "dotnet")]
+  fn dotnet_run(db: &Database, name: &str, options: CSharpOptions, program: &str) -> Result<()> {
+    let out = generate(db, name, options)?;
+    let data = std::env::temp_dir().join(format!("csharp_test_{name}_data"));
+    let _ = remove_dir_all(&data);
+    db.generate_data(&data)?;
+    std::fs::write(out.join("Program.cs"), [DATA_ACCESS, program].concat())?;
+    dotnet(&out, "Exe", &["run", "--", data.to_str().unwrap()])
+  }
+
+  /// 新的 `物品.json` 里少了 `货架` 引用的主键, `ReloadTable` 要报错, 原来的数据一点没变
+  #[cfg(feature = "dotnet")]
+  #[test]
+  fn test_dotnet_reload_dangling() -> Result<()> {
+    const PROGRAM: &str = r#"static class Program {
+    static int Main(string[] args) {
+        var access = new DataAccess(args[0]);
+        Cfg.Load(access);
+        var row = Cfg.商店.货架[1];
+        var item = row.item;
+        File.WriteAllText(Path.Join(args[0], "物品.json"), "{\"2\":{\"id\":2}}");
+        try {
+            Cfg.ReloadTable(access, ".物品");
+            return 1;
+        } catch (InvalidDataException) {
+        }
+        if (!ReferenceEquals(Cfg.商店.货架[1], row) || !ReferenceEquals(row.item, item) || !ReferenceEquals(Cfg.物品[1], item)) {
+            return 2;
+        }
+        return 0;
+    }
+}
+"#;
+    let db = references_db()?;
+    for lazy in [false, true] {
+      let options = CSharpOptions {
+        hot_reload: true,
+        lazy,
+        ..CSharpOptions::default()
+      };
+      dotnet_run(
+        &db,
+        &format!("dotnet_reload_dangling_{lazy}"),
+        options,
+        PROGRAM,
+      )?;
+    }
+    Ok(())
+  }
+
+  /// 懒加载时 Load 不报错, 先用到的表报自己的错, Preload 把所有表的错一起报出来
+  #[cfg(feature = "dotnet")]
+  #[test]
+  fn test_dotnet_lazy_dangling() -> Result<()> {
+    const PROGRAM: &str = r#"static class Program {
+    static int Main(string[] args) {
+        File.WriteAllText(Path.Join(args[0], "物品.json"), "{\"2\":{\"id\":2,\"升级\":3}}");
+        Cfg.Load(new DataAccess(args[0]));
+        try {
+            _ = Cfg.商店.货架;
+            return 1;
+        } catch (InvalidDataException e) {
+            if (e.Message.Contains(".物品[2]")) {
+                return 2;
+            }
+        }
+        try {
+            Cfg.Preload();
+            return 3;
+        } catch (InvalidDataException e) {
+            if (!e.Message.Contains(".物品[2].升级 = 3") || !e.Message.Contains(".商店.货架[1].物品 = 1")) {
+                return 4;
+            }
+        }
+        return 0;
+    }
+}
+"#;
+    let options = CSharpOptions {
+      lazy: true,
+      ..CSharpOptions::default()
+    };
+    dotnet_run(&references_db()?, "dotnet_lazy_dangling", options, PROGRAM)
+  }
+
+  /// 互相引用的两行, record 的 ToString, Equals 和 GetHashCode 不会无限递归
+  #[cfg(feature = "dotnet")]
+  #[test]
+  fn test_dotnet_self_reference() -> Result<()> {
+    const PROGRAM: &str = r#"static class Program {
+    static int Main(string[] args) {
+        Cfg.Load(new DataAccess(args[0]));
+        var a = Cfg.物品[1];
+        var b = Cfg.物品[2];
+        if (!ReferenceEquals(a.升级_ref, b) || !ReferenceEquals(b.升级_ref, a)) {
+            return 1;
+        }
+        var set = new HashSet<object> { a, b };
+        if (set.Count != 2 || a.Equals(b) || a.ToString().Length == 0) {
+            return 2;
+        }
+        return 0;
+    }
+}
+"#;
+    let db = references_db()?;
+    for (mutability, lazy) in [
+      (Mutability::Mutable, false),
+      (Mutability::Immutable, false),
+      (Mutability::Immutable, true),
+    ] {
+      let options = CSharpOptions {
+        mutability,
+        lazy,
+        ..CSharpOptions::default()
+      };
+      dotnet_run(
+        &db,
+        &format!("dotnet_self_reference_{mutability}_{lazy}"),
+        options,
+        PROGRAM,
+      )?;
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_source_gen() -> Result<()> {
+    let db = load_test_proj()?;
+    let options = CSharpOptions {
+      source_gen: true,
+      ..CSharpOptions::default()
+    };
+    let out = generate(&db, "source_gen", options)?;
+    let context = read_to_string(out.join("Common/JsonContext.cs"))?;
+    assert!(
+      context
+        .contains("[JsonSerializable(typeof(Dictionary<int, __Gen.Cfg.Types.掉落.掉落_item>))]")
+    );
+    assert!(context.contains("public sealed class TupleConverter0 : JsonConverter<(int, int)>"));
+    let util = read_to_string(out.join("Common/Util.cs"))?;
+    assert!(!util.contains("MakeGenericType"));
+    Ok(())
+  }
+  #[test]
+  fn test_mutability() -> Result<()> {
+    let db = load_test_proj()?;
+    let options = CSharpOptions {
+      mutability: Mutability::ReadOnly,
+      alias_style: AliasStyle::Struct,
+      ..CSharpOptions::default()
+    };
+    let out = generate(&db, "mutability", options.clone())?;
+    let class = read_to_string(out.join("Types/掉落/掉落_item.cs"))?;
+    assert!(class.contains("public partial record 掉落_item {"));
+    assert!(class.contains("public int id { get; init; }"));
+    assert!(!class.contains("[]"));
+    assert!(
+      read_to_string(out.join("Types/Rewards.cs"))?
+        .contains("public readonly record struct Rewards(IReadOnlyList<")
+    );
+    assert!(
+      read_to_string(out.join("Common/Fx16_16.cs"))?.contains("public readonly struct Fx16_16")
+    );
+    let module = read_to_string(out.join("Types/Mod.cs"))?;
+    assert!(module.contains(
+      "public IReadOnlyDictionary<int, __Gen.Cfg.Types.掉落.掉落_item> 掉落 { get; private set; }"
+    ));
+
+    let options = CSharpOptions {
+      mutability: Mutability::Immutable,
+      ..options
+    };
+    let out = generate(&db, "immutable", options)?;
+    let rewards = read_to_string(out.join("Types/Rewards.cs"))?;
+    assert!(rewards.contains(
+      "public readonly record struct Rewards(System.Collections.Immutable.ImmutableArray<"
+    ));
+    let module = read_to_string(out.join("Types/Mod.cs"))?;
+    assert!(module.contains(
+      "public System.Collections.Immutable.ImmutableDictionary<int, __Gen.Cfg.Types.掉落.掉落_item> 掉落 { get; private set; }"
+    ));
+    Ok(())
+  }
+  #[test]
+  fn test_lazy() -> Result<()> {
+    let db = load_test_proj()?;
+    let options = CSharpOptions {
+      lazy: true,
+      ..CSharpOptions::default()
+    };
+    let out = generate(&db, "lazy", options)?;
+    let module = read_to_string(out.join("Types/Mod.cs"))?;
+    assert!(
+      module
+        .contains("public Dictionary<int, __Gen.Cfg.Types.掉落.掉落_item> 掉落 => _lazy2.Value;")
+    );
+    assert!(module.contains("命名空间.CollectPreload(loads);"));
+    assert!(read_to_string(out.join("Cfg.cs"))?.contains("public static void Preload("));
+    Ok(())
+  }
+
+  #[test]
+  fn test_async_load() -> Result<()> {
+    let db = load_test_proj()?;
+    let options = CSharpOptions {
+      async_load: true,
+      ..CSharpOptions::default()
+    };
+    let out = generate(&db, "async_load", options)?;
+    assert!(
+      read_to_string(out.join("Common/IDataAccess.cs"))?
+        .contains("public interface IDataAccessAsync")
+    );
+    assert!(
+      read_to_string(out.join("Common/Util.cs"))?
+        .contains("JsonSerializer.DeserializeAsync<T>(stream")
+    );
+    let module = read_to_string(out.join("Types/Mod.cs"))?;
+    assert!(module.contains("using System.Threading.Tasks;"));
+    assert!(module.contains("tasks.Add(LoadModule0());"));
+    assert!(read_to_string(out.join("Cfg.cs"))?.contains("public static async Task LoadAsync("));
+    Ok(())
+  }
+
+  #[test]
+  fn test_table_class() -> Result<()> {
+    let mut db = Database::new();
+    db.add_table(RawTable::from_grid(
+      ".物品",
+      &[
+        vec!["", "id", "name", "类型", "标签"],
+        vec!["", "i", "s", "i?", "s"],
+        vec!["标注", "", "unique", "index", ""],
+        vec!["", "1", "a", "1", "x"],
+      ],
+    )?)?;
+    let options = CSharpOptions {
+      table_class: true,
+      ..CSharpOptions::default()
+    };
+    let out = generate(&db, "table_class", options.clone())?;
+    let table = read_to_string(out.join("Types/物品/物品_table.cs"))?;
+    assert!(table.contains("public __Gen.Cfg.Types.物品.物品_item ByName(string key) {"));
+    assert!(table.contains("public bool TryByName(string key,"));
+    assert!(
+      table.contains("public IReadOnlyList<__Gen.Cfg.Types.物品.物品_item> GroupBy类型(int key) {")
+    );
+    assert!(table.contains("if (row.类型 is not { } key) {"));
+    let module = read_to_string(out.join("Types/Mod.cs"))?;
+    assert!(module.contains("public __Gen.Cfg.Types.物品.物品_table 物品;"));
+
+    let mut db = Database::new();
+    db.add_table(RawTable::from_grid(
+      ".物品",
+      &[
+        vec!["", "id", "标签"],
+        vec!["", "i", "l<s>"],
+        vec!["标注", "", "index"],
+        vec!["", "1", ""],
+      ],
+    )?)?;
+    let err = generate(&db, "table_class_error", options).unwrap_err();
+    assert!(err.to_string().contains("不能建索引"), "{err}");
+    Ok(())
+  }
+
+  #[test]
+  fn test_references() -> Result<()> {
+    let db = references_db()?;
+    let out = generate(&db, "references", CSharpOptions::default())?;
+    let class = read_to_string(out.join("Types/商店/货架/货架_item.cs"))?;
+    assert!(class.contains("[JsonIgnore]\n    public __Gen.Cfg.Types.物品.物品_item item;"));
+    assert!(class.contains("public __Gen.Cfg.Types.物品.物品_item? 赠品_ref;"));
+    assert!(class.contains("public IReadOnlyList<__Gen.Cfg.Types.物品.物品_item> 组合_ref;"));
+    let cfg = read_to_string(out.join("Cfg.cs"))?;
+    assert!(cfg.contains(
+      "rootMod = __Gen.Cfg.Types.Mod.Load(access, access.RootPath());\n        Link(rootMod);"
+    ));
+    assert!(cfg.contains("foreach (var pair in mod.商店.货架) {"));
+    assert!(cfg.contains("if (mod.物品.TryGetValue(key, out var item)) {"));
+    assert!(cfg.contains("if (row.赠品 is not { } key) {"));
+
+    let options = CSharpOptions {
+      lazy: true,
+      ..CSharpOptions::default()
+    };
+    let out = generate(&db, "references_lazy", options)?;
+    let cfg = read_to_string(out.join("Cfg.cs"))?;
+    assert!(cfg.contains(
+      "rootMod = __Gen.Cfg.Types.Mod.Load(access, access.RootPath());\n        SetupLinks(rootMod);"
+    ));
+    assert!(cfg.contains("mod._link0 = () => Ensure(0);"));
+    assert!(cfg.contains("mod.商店._link0 = () => Ensure(1);"));
+    // 对一张表时不连带对别的表, 对完再去用指向的表
+    assert!(cfg.contains("foreach (var pair in mod.商店._lazy0.Value) {"));
+    assert!(cfg.contains("if (mod._lazy0.Value.TryGetValue(key, out var item)) {"));
+    assert!(
+      cfg.contains("            case 1:\n                _ = mod.物品;\n                break;")
+    );
+    assert!(cfg.contains("            } catch (System.IO.InvalidDataException e) {\n                errors.Add(e.Message);"));
+    let module = read_to_string(out.join("Types/商店/Mod.cs"))?;
+    assert!(module.contains("System.Threading.Volatile.Read(ref _link0)?.Invoke();"));
+
+    // record 的值里没有 Link 填上的行
+    let options = CSharpOptions {
+      mutability: Mutability::Immutable,
+      ..CSharpOptions::default()
+    };
+    let out = generate(&db, "references_immutable", options)?;
+    let class = read_to_string(out.join("Types/物品/物品_item.cs"))?;
+    assert!(
+      class.contains("public __Gen.Cfg.Types.物品.物品_item? 升级_ref { get; internal set; }")
+    );
+    assert!(class.contains("builder.Append(\", 升级 = \").Append(升级);"));
+    assert!(class.contains("public virtual bool Equals(物品_item? other) {"));
+    assert!(!class.contains("升级_ref)"));
+    Ok(())
+  }
+
+  #[test]
+  fn test_hot_reload() -> Result<()> {
+    let db = load_test_proj()?;
+    let options = CSharpOptions {
+      hot_reload: true,
+      lazy: true,
+      ..CSharpOptions::default()
+    };
+    let out = generate(&db, "hot_reload", options)?;
+    let cfg = read_to_string(out.join("Cfg.cs"))?;
+    assert!(cfg.contains("public static event Action<string?>? OnReloaded;"));
+    assert!(
+      cfg
+        .contains("var mod = rootMod.ReloadTable(access, access.RootPath(), name.TrimStart('.'));")
+    );
+    assert!(cfg.contains("System.Threading.Volatile.Write(ref rootMod, mod);"));
+    let module = read_to_string(out.join("Types/Mod.cs"))?;
+    assert!(module.contains("case \"掉落\" when dot < 0: {"));
+    assert!(module.contains(
+      "tables._lazy2 = new Lazy<Dictionary<int, __Gen.Cfg.Types.掉落.掉落_item>>(data);"
+    ));
+    assert!(module.contains("case \"命名空间\" when dot >= 0:"));
+
+    // 重新读 .物品 时引用了它的 .商店.货架 也重新读, 只对新读的表
+    let options = CSharpOptions {
+      hot_reload: true,
+      ..CSharpOptions::default()
+    };
+    let out = generate(&references_db()?, "hot_reload_references", options)?;
+    let cfg = read_to_string(out.join("Cfg.cs"))?;
+    assert!(cfg.contains(
+      "        case \"物品\":\n            mod = mod.ReloadTable(access, access.RootPath(), \"商店.货架\");\n            Link(mod, new int[] { 0, 1 });"
+    ));
+    assert!(cfg.contains("        case \"商店.货架\":\n            Link(mod, new int[] { 1 });"));
+    Ok(())
+  }
+
+  #[test]
+  fn test_unity() -> Result<()> {
+    let options = CSharpOptions {
+      profile: Profile::Unity,
+      scriptable_object: true,
+      ..CSharpOptions::default()
+    };
+    let err = generate(&load_test_proj()?, "unity_object", options.clone()).unwrap_err();
+    assert!(err.to_string().contains("object 写法的字典"), "{err}");
+
+    let entries = Options {
+      dict_layout: DictLayout::Entries,
+      ..Options::default()
+    };
+    let mut db = Database::with_options(entries.clone());
+    db.load_project("./test/proj/")?;
+    let out = generate(&db, "unity", options.clone())?;
+    let class = read_to_string(out.join("Types/掉落/掉落_item.cs"))?;
+    assert!(class.contains("namespace __Gen.Cfg.Types.掉落 {"));
+    assert!(class.contains("[Serializable]"));
+    assert!(!class.contains("System.Text.Json"));
+    let module = read_to_string(out.join("Types/Mod.cs"))?;
+    assert!(module.contains(
+      "public __Gen.Cfg.Common.SerializableDictionary<int, __Gen.Cfg.Types.掉落.掉落_item> 掉落;"
+    ));
+    assert!(
+      read_to_string(out.join("Common/Util.cs"))?.contains("JsonConvert.DeserializeObject<T>")
+    );
+    assert!(
+      read_to_string(out.join("Types/掉落Asset.cs"))?
+        .contains("public class 掉落Asset : ScriptableObject")
+    );
+    assert!(
+      read_to_string(out.join("Editor/CfgAssetImporter.cs"))?
+        .contains("LoadJson(\"命名空间/a.json\")")
+    );
+
+    // 测试项目里有元组和定点数
+    let options = CSharpOptions {
+      unity_json: UnityJson::JsonUtility,
+      ..options
+    };
+    let err = generate(&db, "unity_error", options.clone()).unwrap_err();
+    assert!(err.to_string().contains("JsonUtility 不支持"), "{err}");
+
+    let mut db = Database::with_options(entries);
+    db.add_table(RawTable::from_grid(
+      ".物品",
+      &[
+        vec!["", "id", "名字", "备注"],
+        vec!["", "i", "s", "s?"],
+        vec!["", "1", "a", ""],
+      ],
+    )?)?;
+    let out = generate(&db, "unity_json_utility", options)?;
+    assert!(read_to_string(out.join("Common/Util.cs"))?.contains("JsonUtility.FromJson<T>"));
+    Ok(())
+  }
+}
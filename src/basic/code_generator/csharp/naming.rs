@@ -0,0 +1,150 @@
+//! C# 的标识符. 表头和文件夹名里什么字符都可能有, 要处理成能编译的名字
+
+use anyhow::Result;
+use strum::{Display, EnumString};
+
+use super::error::Error::*;
+use crate::{
+  HashMap,
+  basic::code_generator::{helpers, unicode},
+};
+
+/// 不能直接当标识符用的关键字, 前面加 `@`
+#[rustfmt::skip]
+const KEYWORDS: &[&str] = &[
+  "abstract", "as", "base", "bool", "break", "byte", "case", "catch", "char", "checked", "class",
+  "const", "continue", "decimal", "default", "delegate", "do", "double", "else", "enum", "event",
+  "explicit", "extern", "false", "finally", "fixed", "float", "for", "foreach", "goto", "if",
+  "implicit", "in", "int", "interface", "internal", "is", "lock", "long", "namespace", "new",
+  "null", "object", "operator", "out", "override", "params", "private", "protected", "public",
+  "readonly", "ref", "return", "sbyte", "sealed", "short", "sizeof", "stackalloc", "static",
+  "string", "struct", "switch", "this", "throw", "true", "try", "typeof", "uint", "ulong",
+  "unchecked", "unsafe", "ushort", "using", "virtual", "void", "volatile", "while",
+];
+
+/// 名字的大小写, `keep` 是表里写的什么就是什么
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Casing {
+  #[default]
+  Keep,
+  Pascal,
+  Camel,
+  Snake,
+}
+
+impl Casing {
+  pub fn apply(self, name: &str) -> String {
+    match self {
+      Casing::Keep => name.to_string(),
+      Casing::Pascal => helpers::pascal_case(name),
+      Casing::Camel => helpers::camel_case(name),
+      Casing::Snake => helpers::snake_case(name),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NamingOptions {
+  /// 表的行类型和别名
+  pub class: Casing,
+  /// 字段, 包括 `Mod` 里的表和子模块
+  pub field: Casing,
+  /// 文件夹对应的命名空间
+  pub namespace: Casing,
+}
+
+/// 开头: 字母和 `_`
+fn is_start(c: char) -> bool {
+  unicode::is_letter(c) || c == '_'
+}
+
+/// 后面: 再加上数字, 组合符号, 连接符和格式字符
+fn is_part(c: char) -> bool {
+  is_start(c) || unicode::is_mark_digit_connector(c) || unicode::is_format(c)
+}
+
+/// 改大小写, 不合法的字符换成 `_`, 不能开头的字符前面加 `_`, 关键字前面加 `@`
+pub fn identifier(name: &str, casing: Casing) -> String {
+  let mut ident = casing
+    .apply(name)
+    .chars()
+    .map(|c| if is_part(c) { c } else { '_' })
+    .collect::<String>();
+  if !ident.starts_with(is_start) {
+    ident.insert(0, '_');
+  }
+  if KEYWORDS.contains(&ident.as_str()) {
+    ident.insert(0, '@');
+  }
+  ident
+}
+
+/// 同一个类或命名空间里的名字, 不同的原名处理成同一个标识符时报错
+pub struct Scope {
+  name: String,
+  /// 标识符到原名
+  idents: HashMap<String, String>,
+}
+
+impl Scope {
+  pub fn new(name: impl Into<String>) -> Self {
+    Self {
+      name: name.into(),
+      idents: HashMap::new(),
+    }
+  }
+
+  /// 成员不能和所在的类同名, 先占上
+  pub fn with_reserved(mut self, ident: &str) -> Self {
+    self.idents.insert(ident.to_string(), ident.to_string());
+    self
+  }
+
+  pub fn add(&mut self, original: &str, casing: Casing) -> Result<String> {
+    let ident = identifier(original, casing);
+    match self.idents.get(ident.trim_start_matches('@')) {
+      Some(other) => Err(
+        标识符冲突(
+          self.name.clone(),
+          other.clone(),
+          original.to_string(),
+          ident,
+        )
+        .into(),
+      ),
+      None => {
+        self.idents.insert(
+          ident.trim_start_matches('@').to_string(),
+          original.to_string(),
+        );
+        Ok(ident)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_identifier() {
+    assert_eq!(identifier("class", Casing::Keep), "@class");
+    assert_eq!(identifier("1st_skill", Casing::Keep), "_1st_skill");
+    assert_eq!(identifier("my-field", Casing::Keep), "my_field");
+    assert_eq!(identifier("my-field", Casing::Pascal), "MyField");
+    assert_eq!(identifier("技能", Casing::Pascal), "技能");
+    assert_eq!(identifier("", Casing::Keep), "_");
+    assert_eq!(identifier("x²", Casing::Keep), "x_");
+    assert_eq!(identifier("½", Casing::Keep), "_");
+    assert_eq!(identifier("٣abc", Casing::Keep), "_٣abc");
+    assert_eq!(identifier("नमस्ते", Casing::Keep), "नमस्ते");
+    assert_eq!(identifier("\u{0e34}กิน", Casing::Keep), "_\u{0e34}กิน");
+
+    let mut scope = Scope::new("A").with_reserved("A");
+    assert_eq!(scope.add("my-field", Casing::Keep).unwrap(), "my_field");
+    assert!(scope.add("my_field", Casing::Keep).is_err());
+    assert!(scope.add("a", Casing::Pascal).is_err());
+  }
+}
@@ -0,0 +1,139 @@
+//! 模板里能用的 helper, 所有生成器共用
+//!
+//! - `{{pascal_case name}}`, `{{camel_case name}}`, `{{snake_case name}}`, `{{upper_snake_case name}}`
+//! - `{{indent 4 text}}`: 每个非空行前面加空格
+//! - `{{join list ", "}}`
+
+use handlebars::{Handlebars, handlebars_helper};
+
+/// 按 `_`, `-`, 空格, 小写到大写的地方拆词. 中文之类没有大小写的字符原样留在词里
+pub fn split_words(s: &str) -> Vec<String> {
+  let mut words = Vec::new();
+  let mut word = String::new();
+  let mut prev_lower = false;
+  for c in s.chars() {
+    if c == '_' || c == '-' || c.is_whitespace() {
+      if !word.is_empty() {
+        words.push(std::mem::take(&mut word));
+      }
+      prev_lower = false;
+      continue;
+    }
+    if c.is_uppercase() && prev_lower && !word.is_empty() {
+      words.push(std::mem::take(&mut word));
+    }
+    prev_lower = c.is_lowercase() || c.is_ascii_digit();
+    word.push(c);
+  }
+  if !word.is_empty() {
+    words.push(word);
+  }
+  words
+}
+
+fn capitalize(word: &str) -> String {
+  let mut chars = word.chars();
+  match chars.next() {
+    Some(first) => first
+      .to_uppercase()
+      .chain(chars.flat_map(char::to_lowercase))
+      .collect(),
+    None => String::new(),
+  }
+}
+
+/// 例: `item_id` -> `ItemId`
+pub fn pascal_case(s: &str) -> String {
+  split_words(s).iter().map(|w| capitalize(w)).collect()
+}
+
+/// 例: `item_id` -> `itemId`
+pub fn camel_case(s: &str) -> String {
+  split_words(s)
+    .iter()
+    .enumerate()
+    .map(|(i, w)| {
+      if i == 0 {
+        w.to_lowercase()
+      } else {
+        capitalize(w)
+      }
+    })
+    .collect()
+}
+
+/// 例: `ItemId` -> `item_id`
+pub fn snake_case(s: &str) -> String {
+  split_words(s)
+    .iter()
+    .map(|w| w.to_lowercase())
+    .collect::<Vec<_>>()
+    .join("_")
+}
+
+pub fn indent(n: usize, s: &str) -> String {
+  let pad = " ".repeat(n);
+  s.split_inclusive('\n')
+    .map(|line| {
+      if line.trim().is_empty() {
+        line.to_string()
+      } else {
+        pad.clone() + line
+      }
+    })
+    .collect()
+}
+
+handlebars_helper!(pascal_case_helper: |s: str| pascal_case(s));
+handlebars_helper!(camel_case_helper: |s: str| camel_case(s));
+handlebars_helper!(snake_case_helper: |s: str| snake_case(s));
+handlebars_helper!(upper_snake_case_helper: |s: str| snake_case(s).to_uppercase());
+handlebars_helper!(indent_helper: |n: u64, s: str| indent(n as usize, s));
+handlebars_helper!(join_helper: |list: array, sep: str| {
+  list
+    .iter()
+    .map(|v| match v {
+      serde_json::Value::String(s) => s.clone(),
+      v => v.to_string(),
+    })
+    .collect::<Vec<_>>()
+    .join(sep)
+});
+
+pub fn register(reg: &mut Handlebars) {
+  reg.register_helper("pascal_case", Box::new(pascal_case_helper));
+  reg.register_helper("camel_case", Box::new(camel_case_helper));
+  reg.register_helper("snake_case", Box::new(snake_case_helper));
+  reg.register_helper("upper_snake_case", Box::new(upper_snake_case_helper));
+  reg.register_helper("indent", Box::new(indent_helper));
+  reg.register_helper("join", Box::new(join_helper));
+}
+
+#[cfg(test)]
+mod test {
+  use handlebars::Handlebars;
+  use serde_json::json;
+
+  use super::*;
+
+  #[test]
+  fn test_case() {
+    assert_eq!(pascal_case("item_id"), "ItemId");
+    assert_eq!(pascal_case("dropItemID"), "DropItemId");
+    assert_eq!(camel_case("Item-Id"), "itemId");
+    assert_eq!(snake_case("dropItem2Count"), "drop_item2_count");
+    assert_eq!(pascal_case("技能_列表"), "技能列表");
+  }
+
+  #[test]
+  fn test_helpers() -> anyhow::Result<()> {
+    let mut reg = Handlebars::new();
+    register(&mut reg);
+    let out = reg.render_template(
+      "{{pascal_case a}}|{{join list \", \"}}|{{indent 2 b}}",
+      &json!({"a": "my_field", "list": ["x", 1], "b": "p\n\nq"}),
+    )?;
+    assert_eq!(out, "MyField|x, 1|  p\n\n  q");
+    Ok(())
+  }
+}
@@ -0,0 +1,336 @@
+use std::{
+  fs::{create_dir_all, write},
+  path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+  HashMap, IndexMap,
+  basic::{
+    code_generator::{self, CodeGenerator, unicode},
+    config::{self, DictLayout},
+    database::{Database, Type},
+  },
+};
+use error::Error::*;
+
+/// 注册到 [super::Registry] 里的 `typescript`, 只生成类型声明, 一个文件 `cfg.ts`
+#[derive(Debug, Clone, Default)]
+pub struct TypeScriptGenerator {
+  /// 里面的 `.hbs` 覆盖同名的内置模板
+  pub template_dir: Option<PathBuf>,
+}
+
+/// 内置模板, 见 [code_generator::new_registry]
+const TEMPLATES: &[(&str, &str)] =
+  &[("cfg", include_str!("../../../templates/typescript/cfg.hbs"))];
+
+impl CodeGenerator for TypeScriptGenerator {
+  fn name(&self) -> &str {
+    "typescript"
+  }
+
+  fn generate(&self, database: &Database, target: &Path) -> Result<()> {
+    let reg = code_generator::new_registry(TEMPLATES, self.template_dir.as_deref())?;
+
+    // 按所在模块分组, 根模块的类型不用套 namespace
+    let mut namespaces: IndexMap<String, NamespaceEnv> = IndexMap::new();
+    for ty in database.types.iter() {
+      match ty {
+        Type::Struct {
+          full_name,
+          fields,
+          meta,
+        } => {
+          let fields = fields
+            .iter()
+            .map(|(name, &tid)| FieldEnv {
+              key: quote(name),
+              typ: ts_type(database, tid),
+              description: meta.get(name).and_then(|m| m.description.clone()),
+            })
+            .collect();
+          let namespace = namespace(&mut namespaces, full_name);
+          let name = namespace.add_name(full_name)?;
+          namespace.interfaces.push(InterfaceEnv { name, fields });
+        }
+        Type::Alias { full_name, target } => {
+          let namespace = namespace(&mut namespaces, full_name);
+          let name = namespace.add_name(full_name)?;
+          namespace.aliases.push(AliasEnv {
+            name,
+            typ: ts_type(database, *target),
+          });
+        }
+        _ => {}
+      }
+    }
+
+    let tables = database
+      .tables()
+      .map(|table| {
+        let name = &table.full_name()[config::PATH_SPLITOR.len_utf8()..];
+        TableEnv {
+          key: quote(name),
+          path: name.replace(config::PATH_SPLITOR, "/") + ".json",
+          typ: ts_type(database, table.data().typ),
+        }
+      })
+      .collect();
+
+    let env = FileEnv {
+      namespaces: namespaces.into_values().collect(),
+      tables,
+    };
+    create_dir_all(target)?;
+    write(target.join("cfg.ts"), reg.render("cfg", &env)?)?;
+    Ok(())
+  }
+}
+
+/// 类型所在模块的 namespace, 第一次用到时建立. 处理成同一个名字的模块共用一个 namespace
+fn namespace<'a>(
+  namespaces: &'a mut IndexMap<String, NamespaceEnv>,
+  full_name: &str,
+) -> &'a mut NamespaceEnv {
+  let module = config::path_parent(full_name);
+  let name = (!module.is_empty()).then(|| ts_path(module));
+  namespaces
+    .entry(name.clone().unwrap_or_default())
+    .or_insert_with(|| NamespaceEnv {
+      name,
+      interfaces: Vec::new(),
+      aliases: Vec::new(),
+      idents: HashMap::new(),
+    })
+}
+
+/// 保留字和内置类型的名字, 不能当 namespace, interface 或 type 的名字
+#[rustfmt::skip]
+const KEYWORDS: &[&str] = &[
+  "any", "bigint", "boolean", "break", "case", "catch", "class", "const", "continue", "debugger",
+  "default", "delete", "do", "else", "enum", "export", "extends", "false", "finally", "for",
+  "function", "if", "implements", "import", "in", "instanceof", "interface", "let", "never", "new",
+  "null", "number", "object", "package", "private", "protected", "public", "return", "static",
+  "string", "super", "switch", "symbol", "this", "throw", "true", "try", "typeof", "undefined",
+  "unknown", "var", "void", "while", "with", "yield",
+];
+
+/// 不合法的字符换成 `_`, 不能开头的字符和关键字前面加 `_`
+fn identifier(name: &str) -> String {
+  let is_start = |c: char| unicode::is_letter(c) || c == '_' || c == '$';
+  let mut ident = name
+    .chars()
+    .map(|c| {
+      if is_start(c) || unicode::is_mark_digit_connector(c) {
+        c
+      } else {
+        '_'
+      }
+    })
+    .collect::<String>();
+  if !ident.starts_with(is_start) || KEYWORDS.contains(&ident.as_str()) {
+    ident.insert(0, '_');
+  }
+  ident
+}
+
+/// `.a.b` 写成 `a.b`, 每一段都处理成标识符
+fn ts_path(full_name: &str) -> String {
+  config::path_components(full_name)[1..]
+    .iter()
+    .map(|s| identifier(s))
+    .collect::<Vec<_>>()
+    .join(".")
+}
+
+/// 属性名都加引号, 中文和关键字都不用特殊处理
+fn quote(name: &str) -> String {
+  serde_json::Value::from(name).to_string()
+}
+
+/// 和导出的 json 对应. 时间都是字符串, 定点数是原始整数.
+/// 字典按 [DictLayout::Object] 写时键都是字符串, 按 [DictLayout::Entries] 写时是键值对的列表
+fn ts_type(database: &Database, tid: usize) -> String {
+  match database.get_type(tid).unwrap() {
+    Type::Unknown | Type::Placeholder(_) => unreachable!("加载完成后不会有未定义的类型"),
+    Type::Dynamic => "unknown".to_string(),
+    Type::Int | Type::Float | Type::Fixed { .. } => "number".to_string(),
+    Type::String | Type::DateTime | Type::Date | Type::Duration => "string".to_string(),
+    Type::Bool => "boolean".to_string(),
+    &Type::Optional(pid) => ts_type(database, pid) + " | null",
+    &Type::List(pid) => match database.get_type(database.resolve_alias(pid)) {
+      Some(Type::Optional(_)) => format!("({})[]", ts_type(database, pid)),
+      _ => ts_type(database, pid) + "[]",
+    },
+    &Type::Dict(kid, pid) => match database.options.dict_layout {
+      DictLayout::Object => format!("Record<string, {}>", ts_type(database, pid)),
+      DictLayout::Entries => format!(
+        "{{ entries: {{ key: {}; value: {} }}[] }}",
+        ts_type(database, kid),
+        ts_type(database, pid)
+      ),
+    },
+    Type::Tuple { items, names } => {
+      // 元组的元素要么都有名字, 要么都没有
+      let labeled = names.iter().all(Option::is_some);
+      let items = items
+        .iter()
+        .zip(names)
+        .map(|(&tid, name)| match name {
+          Some(name) if labeled => format!("{}: {}", identifier(name), ts_type(database, tid)),
+          _ => ts_type(database, tid),
+        })
+        .collect::<Vec<_>>();
+      format!("[{}]", items.join(", "))
+    }
+    Type::Struct { full_name, .. } | Type::Alias { full_name, .. } => ts_path(full_name),
+  }
+}
+
+#[derive(Debug, Serialize)]
+struct FileEnv {
+  namespaces: Vec<NamespaceEnv>,
+  tables: Vec<TableEnv>,
+}
+
+#[derive(Debug, Serialize)]
+struct NamespaceEnv {
+  name: Option<String>,
+  interfaces: Vec<InterfaceEnv>,
+  aliases: Vec<AliasEnv>,
+  /// 标识符到原来的全名
+  #[serde(skip)]
+  idents: HashMap<String, String>,
+}
+
+impl NamespaceEnv {
+  /// 不同的类型处理成同一个标识符时报错, 不然 TypeScript 会把两个 interface 合并
+  fn add_name(&mut self, full_name: &str) -> Result<String> {
+    let ident = identifier(config::path_name(full_name));
+    match self.idents.get(&ident) {
+      Some(other) => Err(标识符冲突(other.clone(), full_name.to_string(), ident).into()),
+      None => {
+        self.idents.insert(ident.clone(), full_name.to_string());
+        Ok(ident)
+      }
+    }
+  }
+}
+
+#[derive(Debug, Serialize)]
+struct InterfaceEnv {
+  name: String,
+  fields: Vec<FieldEnv>,
+}
+
+#[derive(Debug, Serialize)]
+struct FieldEnv {
+  key: String,
+  typ: String,
+  description: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct AliasEnv {
+  name: String,
+  typ: String,
+}
+
+#[derive(Debug, Serialize)]
+struct TableEnv {
+  key: String,
+  path: String,
+  typ: String,
+}
+
+pub mod error {
+  use thiserror::Error;
+
+  #[derive(Debug, Error)]
+  pub enum Error {
+    #[error("`{0}` 和 `{1}` 的 TypeScript 标识符都是 `{2}`")]
+    标识符冲突(String, String, String),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::read_to_string;
+
+  use anyhow::Result;
+
+  use crate::basic::{
+    code_generator::{CodeGenerator, typescript::TypeScriptGenerator},
+    config::{DictLayout, Options},
+    database::Database,
+    raw_table::RawTable,
+  };
+
+  #[test]
+  fn test_dict_layout() -> Result<()> {
+    for (dict_layout, expected) in [
+      (
+        DictLayout::Object,
+        "\"物品\": Record<string, 物品.物品_item>;",
+      ),
+      (
+        DictLayout::Entries,
+        "\"物品\": { entries: { key: number; value: 物品.物品_item }[] };",
+      ),
+    ] {
+      let mut db = Database::with_options(Options {
+        dict_layout,
+        ..Options::default()
+      });
+      db.add_table(RawTable::from_rows(".物品", &["id"], &["i"], &[vec!["1"]])?)?;
+      let out = std::env::temp_dir().join(format!("typescript_test_{dict_layout}"));
+      TypeScriptGenerator::default().generate(&db, &out)?;
+      let cfg = read_to_string(out.join("cfg.ts"))?;
+      assert!(cfg.contains(expected), "{cfg}");
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_identifier() -> Result<()> {
+    let mut db = Database::new();
+    db.add_table(RawTable::from_rows(
+      ".my-mod.1st",
+      &["id", "v"],
+      &["i", "t<i class, i b>"],
+      &[vec!["1", "(1, 2)"]],
+    )?)?;
+    let out = std::env::temp_dir().join("typescript_test_identifier");
+    TypeScriptGenerator::default().generate(&db, &out)?;
+    let cfg = read_to_string(out.join("cfg.ts"))?;
+    assert!(cfg.contains("export namespace my_mod._1st {"), "{cfg}");
+    assert!(cfg.contains("export interface _1st_item {"), "{cfg}");
+    assert!(cfg.contains("\"v\": [_class: number, b: number];"), "{cfg}");
+    assert!(
+      cfg.contains("Record<string, my_mod._1st._1st_item>"),
+      "{cfg}"
+    );
+
+    // `x-y` 和 `x_y` 的行类型都是 `a.x_y.x_y_item`
+    db.add_table(RawTable::from_rows(
+      ".a.x-y",
+      &["id"],
+      &["i"],
+      &[vec!["1"]],
+    )?)?;
+    db.add_table(RawTable::from_rows(
+      ".a.x_y",
+      &["id"],
+      &["i"],
+      &[vec!["1"]],
+    )?)?;
+    let err = TypeScriptGenerator::default()
+      .generate(&db, &out)
+      .unwrap_err();
+    assert!(err.to_string().contains("标识符都是 `x_y_item`"), "{err}");
+    Ok(())
+  }
+}
@@ -1,12 +1,7 @@
-use crate::HashMap;
-use anyhow::Result;
-use std::{
-  borrow::Borrow,
-  cell::{LazyCell, OnceCell},
-  hash::Hash,
-  path::Path,
-  sync::LazyLock,
-};
+use crate::{HashMap, basic::fixed::Rounding};
+use chrono::FixedOffset;
+use std::{borrow::Borrow, path::Path, sync::LazyLock};
+use strum::{Display, EnumString};
 
 pub trait OptionJoin {
   fn option_join(&self, sep: &str) -> String;
@@ -46,26 +41,47 @@ pub fn path_name(path: &str) -> &str {
   }
 }
 
+/// 表的全名以 `.` 开头, 每一段都不为空. 例: `.命名空间.a`
+pub fn is_full_name(path: &str) -> bool {
+  path
+    .strip_prefix(PATH_SPLITOR)
+    .is_some_and(|rest| rest.split(PATH_SPLITOR).all(|name| !name.is_empty()))
+}
+
 pub fn path_rel_to_global(rel_path: &str) -> String {
   path_join(&["", rel_path])
 }
 
-pub const GENERIC_SPLITOR: char = '-';
-pub fn generic_type_name(base: &str, type_params: &[usize]) -> String {
-  let mut v = vec![base.to_string()];
-  v.extend(type_params.iter().map(|i| i.to_string()));
-  v.join(&GENERIC_SPLITOR.to_string())
+pub const GENERIC_SPLITOR: &str = ", ";
+/// 泛型类型的名字, 由类型参数的全名拼成, 和加载顺序无关. 例: `dictionary<.int, .a.a_item>`
+pub fn generic_type_name(base: &str, type_params: &[String]) -> String {
+  format!("{base}<{}>", type_params.join(GENERIC_SPLITOR))
+}
+
+pub const FIXED_TYPE_PREFIX: &str = "fx";
+/// 定点数的类型名, 例: `fx16_16`. 不能用 `.`, 会和路径分隔符冲突
+pub fn fixed_type_name(int_bits: u8, frac_bits: u8) -> String {
+  format!("{FIXED_TYPE_PREFIX}{int_bits}_{frac_bits}")
+}
+
+/// 每个文件夹里可以有一个别名文件, 里面的别名属于这个文件夹对应的模块
+pub const ALIAS_FILE_NAME: &str = "_alias.csv";
+
+/// 类型行后面可以接几行说明, meta 列写这几个字的行就是
+pub const DESCRIPTION_ROW_MARK: &str = "描述";
+/// 例: `index`, `unique, ref=.items`
+pub const ANNOTATION_ROW_MARK: &str = "标注";
+/// 格子空着时用的值
+pub const DEFAULT_ROW_MARK: &str = "默认";
+pub fn is_head_row_mark(meta: &str) -> bool {
+  [DESCRIPTION_ROW_MARK, ANNOTATION_ROW_MARK, DEFAULT_ROW_MARK].contains(&meta.trim())
 }
 
 pub const ITEM_POSTFIX: &str = "_item";
 pub fn table_item_type_full_name(table_full_name: &str) -> String {
-  let mod_name = path_components(&table_full_name)
-    .last()
-    .unwrap()
-    .to_string();
+  let mod_name = path_components(table_full_name).last().unwrap().to_string();
   let item_name = mod_name + ITEM_POSTFIX;
-  let full_name = path_join(&[&table_full_name, &item_name]);
-  full_name
+  path_join(&[table_full_name, &item_name])
 }
 
 pub static BUILTIN_TYPE_NAMES: LazyLock<HashMap<&'static str, &'static str>> =
@@ -75,14 +91,48 @@ pub static BUILTIN_TYPE_NAMES: LazyLock<HashMap<&'static str, &'static str>> =
     map.insert("f", "float");
     map.insert("s", "string");
     map.insert("b", "bool");
-    map.insert("l", "list");
-    map.insert("d", "dictionary");
+    map.insert("datetime", "datetime");
+    map.insert("date", "date");
+    map.insert("duration", "duration");
     map
   });
 
-pub const TYPE_PARAMETER_DELIMINATOR_LEFT: &str = "<";
-pub const TYPE_PARAMETER_DELIMINATOR_RIGHT: &str = ">";
-pub const TYPE_PARAMETER_SPLITOR: &str = ",";
+/// C# 的 ValueTuple 超过 7 个元素就要嵌套, 干脆不支持
+pub const MAX_TUPLE_LEN: usize = 7;
+
+/// 导出选项
+#[derive(Debug, Clone)]
+pub struct Options {
+  /// 没有写时区的 datetime 按这个时区解释
+  pub timezone: FixedOffset,
+  /// 定点数的舍入方式
+  pub rounding: Rounding,
+  /// csv 文件超过这么多字节就流式导出, 不整张读进内存
+  pub stream_threshold: Option<u64>,
+  pub dict_layout: DictLayout,
+}
+
+impl Default for Options {
+  fn default() -> Self {
+    Self {
+      timezone: FixedOffset::east_opt(0).unwrap(),
+      rounding: Rounding::default(),
+      stream_threshold: None,
+      dict_layout: DictLayout::default(),
+    }
+  }
+}
+
+/// 字典 (包括表本身) 在 json 里的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum DictLayout {
+  /// `{"键的json": 值}`, json 的键只能是字符串, 所以键再转一次字符串
+  #[default]
+  Object,
+  /// `{"entries": [{"key": 键, "value": 值}]}`, Unity 的 JsonUtility 和序列化只认这种
+  Entries,
+}
 
 pub fn os_path_to_path(
   root_os_path: impl AsRef<Path>,
@@ -103,6 +153,8 @@ pub fn os_path_to_path(
 mod test {
   use crate::basic::config::os_path_to_path;
 
+  // 只有 windows 认 `\` 是分隔符
+  #[cfg(windows)]
   #[test]
   fn test_os_path_to_path() {
     let name = os_path_to_path("D:\\a\\b\\c", "D:\\a\\b\\c\\d\\e.csv");
@@ -110,4 +162,13 @@ mod test {
     let name = os_path_to_path("D:\\a\\b\\c", "D:\\a\\b\\c\\d\\e");
     assert_eq!(name, Some(".d.e".to_string()));
   }
+
+  #[test]
+  fn test_os_path_to_path_slash() {
+    let name = os_path_to_path("./a/b/c/", "./a/b/c/d/e.csv");
+    assert_eq!(name, Some(".d.e".to_string()));
+    let name = os_path_to_path("/a/b/c", "/a/b/c/d/e");
+    assert_eq!(name, Some(".d.e".to_string()));
+    assert_eq!(os_path_to_path("/a/b/c", "/a/b/cd/e"), None);
+  }
 }
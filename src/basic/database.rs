@@ -1,28 +1,27 @@
 use std::{
-  backtrace::Backtrace,
-  ffi::OsStr,
-  fs::{DirBuilder, File, ReadDir, create_dir, create_dir_all, read_dir, remove_dir, write},
-  ops::Not,
-  path::Path,
-  rc::Rc,
+  fs::{File, create_dir, create_dir_all, read_dir, remove_file, rename},
+  io::{BufWriter, Write},
+  path::{Path, PathBuf},
+  sync::{Arc, OnceLock},
 };
 
 use ego_tree::{NodeId, Tree};
-use serde::de::value;
-use serde_json::{Map, Number, Value};
+use rayon::prelude::*;
+use serde::Serialize;
 use strum::{EnumIs, EnumTryAs};
 
 use crate::{
-  HashMap,
+  HashMap, HashSet, IndexMap,
   basic::{
-    code_generator::csharp::CSharp,
-    config, database,
-    raw_table::{Cell, RawTable},
+    config, parser,
+    query::{self, Key},
+    raw_table::{Cell, RawAlias, RawTable},
+    stream::StreamSource,
+    value::{Span, Value},
   },
 };
-use anyhow::{Result, anyhow, bail};
+use anyhow::Result;
 use error::Error::*;
-use std::io::Write;
 
 #[derive(Debug, Clone, EnumIs, EnumTryAs)]
 pub enum ItemTag {
@@ -51,208 +50,182 @@ impl ItemTag {
 #[derive(Debug, Clone, EnumIs, EnumTryAs)]
 pub enum Type {
   Unknown,
+  /// 已经声明了名字, 还没有定义的类型
   Placeholder(String),
   Dynamic,
   Int,
   Float,
   String,
   Bool,
+  DateTime,
+  Date,
+  Duration,
+  /// 定点数, 整数位包括符号位
+  Fixed {
+    int_bits: u8,
+    frac_bits: u8,
+  },
+  /// 可以不填, 不填时导出 `null`
+  Optional(usize),
   List(usize),
   Dict(usize, usize),
+  /// `names` 和 `items` 一样长, 没有名字的元素是 [None]
+  Tuple {
+    items: Vec<usize>,
+    names: Vec<Option<String>>,
+  },
   Struct {
     full_name: String,
-    fields: HashMap<Rc<String>, usize>,
+    /// 按列的顺序
+    fields: IndexMap<Arc<str>, usize>,
+    /// 表头里的说明行, 没写说明的字段没有
+    meta: IndexMap<Arc<str>, FieldMeta>,
+  },
+  /// 类型别名, 例: `ItemId = i`. 导出数据时和 `target` 完全一样
+  Alias {
+    full_name: String,
+    target: usize,
   },
 }
 
+/// 表头里类型行下面的说明行, 见 [config::is_head_row_mark]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FieldMeta {
+  pub description: Option<String>,
+  /// `index, ref=.items` 写成 `{"index": true, "ref": ".items"}`
+  pub annotations: serde_json::Map<String, serde_json::Value>,
+  /// 格子空着时用的值, 原样保留
+  pub default: Option<String>,
+}
+
+impl FieldMeta {
+  pub fn is_empty(&self) -> bool {
+    self == &Self::default()
+  }
+}
+
 impl Type {
   /// 要求: (否则panic)
   ///
-  /// 不能是 [Type::Unknown] 或 [Type::Placeholder]
-
-  fn get_full_name(&self) -> String {
+  /// 不能是 [Type::Unknown]
+  fn get_full_name(&self, database: &Database) -> String {
+    let name = |id: usize| database.type_full_name(id);
     match self {
-      Type::Unknown | Type::Placeholder(_) => panic!("未知类型"),
+      Type::Unknown => panic!("未知类型"),
+      Type::Placeholder(full_name) => full_name.clone(),
       Type::Dynamic => ".dynamic".to_string(),
       Type::Int => ".int".to_string(),
       Type::Float => ".float".to_string(),
       Type::String => ".string".to_string(),
       Type::Bool => ".bool".to_string(),
-      &Type::List(item_tag) => {
-        let id = item_tag;
-        ".".to_string() + &config::generic_type_name("list", &[id])
+      Type::DateTime => ".datetime".to_string(),
+      Type::Date => ".date".to_string(),
+      Type::Duration => ".duration".to_string(),
+      &Type::Fixed {
+        int_bits,
+        frac_bits,
+      } => ".".to_string() + &config::fixed_type_name(int_bits, frac_bits),
+      &Type::Optional(id) => ".".to_string() + &config::generic_type_name("optional", &[name(id)]),
+      &Type::List(id) => ".".to_string() + &config::generic_type_name("list", &[name(id)]),
+      &Type::Dict(id, id2) => {
+        ".".to_string() + &config::generic_type_name("dictionary", &[name(id), name(id2)])
       }
-      &Type::Dict(key_tag, value_tag) => {
-        let id = key_tag;
-        let id2 = value_tag;
-        ".".to_string() + &config::generic_type_name("dictionary", &[id, id2])
+      Type::Tuple { items, names } => {
+        let params = items
+          .iter()
+          .zip(names)
+          .map(|(&id, n)| match n {
+            Some(n) => format!("{} {n}", name(id)),
+            None => name(id),
+          })
+          .collect::<Vec<_>>();
+        ".".to_string() + &config::generic_type_name("tuple", &params)
       }
-      Type::Struct { full_name, .. } => full_name.clone(),
+      Type::Struct { full_name, .. } | Type::Alias { full_name, .. } => full_name.clone(),
     }
   }
+
+  /// 有自己名字的类型. 其他类型由结构决定, 结构相同就是同一个类型
+  fn is_named(&self) -> bool {
+    matches!(
+      self,
+      Type::Struct { .. } | Type::Alias { .. } | Type::Placeholder(_)
+    )
+  }
 }
 
 #[derive(Debug, Clone, EnumIs, EnumTryAs)]
 pub enum RawData {
   Unknown,
-  One(Cell),
+  One(Cell, Span),
   Many,
-  Struct(HashMap<Rc<String>, NodeId>),
+  Struct(IndexMap<Arc<str>, NodeId>),
 }
 
 #[derive(Debug)]
 pub struct Data {
   pub full_name: String,
   pub typ: usize,
-  pub value: Tree<RawData>,
+  pub content: Content,
+  key_index: OnceLock<HashMap<Key, usize>>,
+}
+
+#[derive(Debug)]
+pub enum Content {
+  /// 检查过的数据, 整张表在内存里
+  Value(Value),
+  /// 太大的表, 导出时再分块读
+  Stream(StreamSource),
 }
 
 impl Data {
-  pub fn build_json(&self, database: &Database) -> Result<Value> {
-    self
-      .bd_json(database, self.typ, self.value.root().id())
-      .and_then(|value| value.ok_or(原始数据值为空(Backtrace::capture()).into()))
+  pub fn new(full_name: &str, typ: usize, content: Content) -> Self {
+    Self {
+      full_name: full_name.to_string(),
+      typ,
+      content,
+      key_index: OnceLock::new(),
+    }
   }
 
-  fn bd_json(&self, database: &Database, typ_id: usize, data_id: NodeId) -> Result<Option<Value>> {
-    let ty = database.get_type(typ_id).ok_or(类型不存在)?;
-    let node = self.value.get(data_id).ok_or(原始数据节点不存在)?;
+  pub(crate) fn key_index(&self) -> &HashMap<Key, usize> {
+    self.key_index.get_or_init(|| match self.value() {
+      Some(value) => query::build_key_index(value),
+      None => HashMap::new(),
+    })
+  }
 
-    let value = match ty {
-      Type::Unknown => return Err(类型未知.into()),
-      Type::Placeholder(_) => return Err(类型没有定义.into()),
-      Type::Dynamic => unimplemented!("目前不支持动态类型"),
-      Type::Int => {
-        let s = node
-          .value()
-          .try_as_one_ref()
-          .ok_or(原始数据节点类型不匹配)?;
-        dbg!(&s);
-        if s.trim().is_empty() {
-          None
-        } else {
-          let v = serde_json::from_str::<Number>(&s)?;
-          if v.is_f64() {
-            return Err(数字类型错误.into());
-          }
-          Some(Value::from(v))
-        }
-      }
-      Type::Float => {
-        let s = node
-          .value()
-          .try_as_one_ref()
-          .ok_or(原始数据节点类型不匹配)?;
-        if s.trim().is_empty() {
-          None
-        } else {
-          let v = serde_json::from_str::<Number>(&s)?;
-          if !v.is_f64() {
-            return Err(数字类型错误.into());
-          }
-          Some(Value::from(v))
-        }
-      }
-      Type::String => {
-        let s = node
-          .value()
-          .try_as_one_ref()
-          .ok_or(原始数据节点类型不匹配)?;
-        dbg!(&s);
-        if s.trim().is_empty() {
-          None
-        } else {
-          let v = serde_json::from_str::<String>(&s).unwrap_or(s.as_ref().to_string());
-          Some(Value::from(v))
-        }
-      }
-      Type::Bool => {
-        let s = node
-          .value()
-          .try_as_one_ref()
-          .ok_or(原始数据节点类型不匹配)?;
-        if s.trim().is_empty() {
-          None
-        } else {
-          let v = serde_json::from_str::<bool>(&s)?;
-          Some(Value::from(v))
-        }
-      }
-      Type::List(tid) => {
-        if !node.value().is_many() {
-          return Err(原始数据节点类型不匹配.into());
-        }
-        let mut v = Vec::new();
-        for ch in node.children() {
-          let item = self.bd_json(database, *tid, ch.id())?;
-          if let Some(item) = item {
-            v.push(item);
-          }
-        }
-        Some(Value::from(v))
-      }
-      Type::Dict(key_tid, value_tid) => {
-        if !node.value().is_many() {
-          return Err(原始数据节点类型不匹配.into());
-        }
-        let mut v = Map::new();
-        for ch in node.children() {
-          let mut entry = ch.children();
-          let key_id = entry.next().ok_or(原始数据节点类型不匹配)?.id();
-          let value_id = entry.next().ok_or(原始数据节点类型不匹配)?.id();
-          if entry.next().is_some() {
-            return Err(原始数据节点类型不匹配.into());
-          }
-          let key = self.bd_json(database, *key_tid, key_id)?;
-          dbg!(&key);
-          if let Some(key) = key {
-            let value = self
-              .bd_json(database, *value_tid, value_id)?
-              .ok_or(原始数据值为空(Backtrace::capture()))?;
-            let key_str = serde_json::to_string(&key)?;
-            v.insert(key_str, value);
-          }
-        }
-        Some(Value::from(v))
-      }
-      Type::Struct { full_name, fields } => {
-        let fields_data = node
-          .value()
-          .try_as_struct_ref()
-          .ok_or(原始数据节点类型不匹配)?;
-        if fields.len() != fields_data.len() {
-          return Err(原始数据节点类型不匹配.into());
-        }
-        let mut v = Map::new();
-        let mut exist_none = false;
-        for (field_name, f_tid) in fields.iter() {
-          let f_id = fields_data.get(field_name).ok_or(原始数据节点类型不匹配)?;
-          let field = self.bd_json(database, *f_tid, *f_id)?;
-          dbg!((&field_name, &field));
-          if let Some(field) = field {
-            v.insert(field_name.as_ref().clone(), field);
-          } else {
-            exist_none = true;
-            break;
-          }
-        }
-        if exist_none {
-          None
-        } else {
-          Some(Value::from(v))
-        }
+  /// 流式的表不在内存里, 返回 [None]. 这样的表只有主键, 能检查引用, 不能查询
+  pub fn value(&self) -> Option<&Value> {
+    match &self.content {
+      Content::Value(value) => Some(value),
+      Content::Stream(_) => None,
+    }
+  }
+
+  /// 流式的表不在内存里, 返回 [None]. 字典按 [config::DictLayout::Object] 写
+  pub fn build_json(&self) -> Option<serde_json::Value> {
+    self.value().map(Value::to_json)
+  }
+
+  pub fn write_json(&self, database: &Database, mut writer: impl Write) -> Result<()> {
+    match &self.content {
+      Content::Value(value) => {
+        serde_json::to_writer(&mut writer, &value.to_json_as(database.options.dict_layout))?
       }
-    };
-    Ok(value)
+      Content::Stream(source) => source.write_json(self, database, &mut writer)?,
+    }
+    writer.flush()?;
+    Ok(())
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Module {
   pub name: String,
-  pub type_name_to_id: HashMap<String, usize>,
+  pub type_name_to_id: IndexMap<String, usize>,
   pub data: Option<usize>,
-  pub children_name_to_id: HashMap<String, NodeId>,
+  pub children_name_to_id: IndexMap<String, NodeId>,
 }
 
 impl Module {
@@ -260,35 +233,54 @@ impl Module {
     Self {
       name: name.to_string(),
       data: None,
-      type_name_to_id: HashMap::new(),
-      children_name_to_id: HashMap::new(),
+      type_name_to_id: IndexMap::new(),
+      children_name_to_id: IndexMap::new(),
     }
   }
 }
 
 #[derive(Debug)]
 pub struct Database {
+  pub options: config::Options,
   pub types: Vec<Type>,
   pub data: Vec<Data>,
   pub modules: Tree<Module>,
 }
 
+impl Default for Database {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl Database {
   pub fn new() -> Self {
+    Self::with_options(config::Options::default())
+  }
+  pub fn with_options(options: config::Options) -> Self {
     let mut res = Self {
+      options,
       types: Vec::new(),
       data: Vec::new(),
       modules: Tree::new(Module::new("")),
     };
-    res.add_type(Type::Int);
-    res.add_type(Type::Float);
-    res.add_type(Type::String);
-    res.add_type(Type::Bool);
+    // 内置类型没有名字, 不会重名
+    for ty in [
+      Type::Int,
+      Type::Float,
+      Type::String,
+      Type::Bool,
+      Type::DateTime,
+      Type::Date,
+      Type::Duration,
+    ] {
+      res.add_type(ty).unwrap();
+    }
     res
   }
   pub fn get_or_create_module(&mut self, module: &str) -> NodeId {
     let mods = config::path_components(module);
-    assert!(mods[0] == "");
+    assert!(mods[0].is_empty());
     let mut mid = self.modules.root().id();
     for &mod_name in &mods[1..] {
       if let Some(&id) = self
@@ -320,7 +312,9 @@ impl Database {
   }
   pub fn get_module(&self, module: &str) -> Option<NodeId> {
     let mods = config::path_components(module);
-    assert!(mods[0] == "");
+    if !mods[0].is_empty() {
+      return None;
+    }
     let mut m = self.modules.root();
     for &mod_name in &mods[1..] {
       let id = m.value().children_name_to_id.get(mod_name)?.to_owned();
@@ -328,12 +322,41 @@ impl Database {
     }
     Some(m.id())
   }
-  pub fn add_type(&mut self, ty: Type) -> usize {
-    let mid = if let Type::Struct { full_name, .. } = &ty {
-      self.get_or_create_module(config::path_parent(&full_name))
-    } else {
-      self.modules.root().id()
+  /// 定义 [Type::Struct] 时, 如果已经有同名的 [Type::Placeholder], 就原地替换, id 不变
+  ///
+  /// 没有名字的类型按结构去重, 结构相同时返回已有的 id.
+  /// 有名字的类型 (结构体, 别名) 和已经有的类型重名时报错
+  pub fn add_type(&mut self, ty: Type) -> Result<usize> {
+    let (mid, name) = match &ty {
+      Type::Struct { full_name, .. }
+      | Type::Alias { full_name, .. }
+      | Type::Placeholder(full_name) => (
+        self.get_or_create_module(config::path_parent(full_name)),
+        config::path_name(full_name).to_string(),
+      ),
+      // 泛型的名字里有 `.`, 不能按路径拆
+      _ => (
+        self.modules.root().id(),
+        ty.get_full_name(self)[config::PATH_SPLITOR.len_utf8()..].to_string(),
+      ),
     };
+    if let Some(&id) = self
+      .modules
+      .get(mid)
+      .unwrap()
+      .value()
+      .type_name_to_id
+      .get(&name)
+    {
+      if !ty.is_named() {
+        return Ok(id);
+      }
+      if !ty.is_placeholder() && self.types[id].is_placeholder() {
+        self.types[id] = ty;
+        return Ok(id);
+      }
+      return Err(类型重名(self.type_full_name(id)).into());
+    }
 
     let id = self.types.len();
     self
@@ -342,20 +365,63 @@ impl Database {
       .unwrap()
       .value()
       .type_name_to_id
-      .insert(config::path_name(&ty.get_full_name()).to_string(), id);
+      .insert(name, id);
     self.types.push(ty);
+    Ok(id)
+  }
+  /// 类型的全名, 泛型的名字由类型参数的全名拼成
+  pub fn type_full_name(&self, id: usize) -> String {
+    self.types[id].get_full_name(self)
+  }
+  /// 穿过别名, 找到实际的类型
+  pub fn resolve_alias(&self, mut id: usize) -> usize {
+    while let Some(Type::Alias { target, .. }) = self.get_type(id) {
+      id = *target;
+    }
     id
   }
-  pub fn add_data(&mut self, data: Data) -> usize {
+
+  /// 定义类型别名, `raw_type` 从别名所在的模块开始查找
+  pub fn add_alias(&mut self, full_name: &str, raw_type: &str) -> Result<usize> {
+    let target = parser::parse_raw_type(raw_type, config::path_parent(full_name), self)
+      .map_err(|e| e.context(format!("类型别名 {full_name} 的类型错误")))?;
+    self.add_type(Type::Alias {
+      full_name: full_name.to_string(),
+      target,
+    })
+  }
+
+  /// 别名不能直接或间接指向自己
+  fn check_alias_cycles(&self) -> Result<()> {
+    for (id, ty) in self.types.iter().enumerate() {
+      if let Type::Alias { full_name, .. } = ty {
+        let mut cur = id;
+        for _ in 0..self.types.len() {
+          match self.get_type(cur) {
+            Some(Type::Alias { target, .. }) => cur = *target,
+            _ => break,
+          }
+          if cur == id {
+            return Err(类型别名循环引用(full_name.clone()).into());
+          }
+        }
+      }
+    }
+    Ok(())
+  }
+
+  pub fn add_data(&mut self, data: Data) -> Result<usize> {
+    if !config::is_full_name(&data.full_name) {
+      return Err(表名错误(data.full_name).into());
+    }
+    if self.table(&data.full_name).is_some() {
+      return Err(表重复(data.full_name).into());
+    }
     let mid = self.get_or_create_module(&data.full_name);
-    assert!(mid != self.modules.root().id());
-    let mut m = self.modules.get_mut(mid).unwrap();
-    let data_loc = &mut m.value().data;
-    assert!(data_loc.is_none());
     let id = self.data.len();
-    *data_loc = Some(id);
+    self.modules.get_mut(mid).unwrap().value().data = Some(id);
     self.data.push(data);
-    id
+    Ok(id)
   }
 
   pub fn get_type_id_by_full_name(&self, name: &str) -> Option<usize> {
@@ -379,108 +445,248 @@ impl Database {
     names.join(&config::PATH_SPLITOR.to_string())
   }
 
+  /// 先读出所有表, 声明每个表的类型, 再逐个解析.
+  /// 这样表头里可以引用任意表的类型, 不受加载顺序影响.
+  /// 读文件和检查数据是多线程的, 结果和单线程一样按路径排序
   pub fn load_project(&mut self, root: impl AsRef<Path>) -> Result<()> {
-    self.ld_project(root.as_ref(), root.as_ref())
+    let mut table_paths = Vec::new();
+    let mut raw_aliases = Vec::new();
+    ld_project(
+      root.as_ref(),
+      root.as_ref(),
+      &mut table_paths,
+      &mut raw_aliases,
+    )?;
+    let stream_threshold = self.options.stream_threshold;
+    let raw_tables = table_paths
+      .par_iter()
+      .map(|(path, full_name)| {
+        // 转置的表没法按行分块, 只能整张读
+        if stream_threshold.is_some_and(|t| path.metadata().is_ok_and(|m| m.len() > t)) {
+          let head = RawTable::from_csv_head(path, full_name)?;
+          if !head.is_transpose() {
+            return Ok((head, Some(path.clone())));
+          }
+        }
+        Ok::<_, anyhow::Error>((RawTable::from_csv(path, full_name)?, None))
+      })
+      .collect::<Vec<_>>()
+      .into_iter()
+      .try_collect::<Vec<_>>()?;
+    self.add_raw(raw_tables, &raw_aliases)?;
+    Ok(())
   }
 
-  fn ld_project(&mut self, root: impl AsRef<Path>, path: impl AsRef<Path>) -> Result<()> {
-    let ch_paths = read_dir(path.as_ref())?
-      .map(|e| Ok::<_, anyhow::Error>(e?.path()))
-      .try_collect::<Vec<_>>()?;
-    // 文件夹内有 .teignore 文件, 则忽略本文件夹
-    if ch_paths
-      .iter()
-      .any(|p| p.is_file() && p.file_name().is_some_and(|f| f == ".teignore"))
-    {
-      return Ok(());
+  /// 不经过文件加一张表, 和读同样内容的 csv 文件一样
+  pub fn add_table(&mut self, raw_table: RawTable) -> Result<usize> {
+    Ok(self.add_tables(vec![raw_table])?[0])
+  }
+
+  /// 一起加的表之间可以互相引用, 返回每张表的数据 id
+  pub fn add_tables(&mut self, raw_tables: Vec<RawTable>) -> Result<Vec<usize>> {
+    self.add_raw(raw_tables.into_iter().map(|t| (t, None)).collect(), &[])
+  }
+
+  /// 有路径的表是流式的, 只读了 head. 出错时类型, 模块和数据都还原成加之前的样子
+  fn add_raw(
+    &mut self,
+    raw_tables: Vec<(RawTable, Option<PathBuf>)>,
+    raw_aliases: &[RawAlias],
+  ) -> Result<Vec<usize>> {
+    let types = self.types.clone();
+    let modules = self.modules.clone();
+    let data_len = self.data.len();
+    let res = self.try_add_raw(raw_tables, raw_aliases);
+    if res.is_err() {
+      self.types = types;
+      self.modules = modules;
+      self.data.truncate(data_len);
     }
+    res
+  }
 
-    for ch_path in ch_paths.iter() {
-      if ch_path.is_file() && ch_path.extension().is_some() && ch_path.extension().unwrap() == "csv"
-      {
-        let full_name =
-          config::os_path_to_path(root.as_ref(), ch_path.clone()).ok_or(文件路径错误)?;
-        dbg!(&full_name);
-        let mut raw_table = RawTable::from_csv(ch_path.clone(), &full_name)?;
-        raw_table.build(self)?;
-      } else {
-        self.ld_project(root.as_ref(), ch_path.clone())?
+  fn try_add_raw(
+    &mut self,
+    raw_tables: Vec<(RawTable, Option<PathBuf>)>,
+    raw_aliases: &[RawAlias],
+  ) -> Result<Vec<usize>> {
+    let mut names = HashSet::new();
+    for (raw_table, _) in raw_tables.iter() {
+      let full_name = raw_table.get_full_name();
+      if self.table(&full_name).is_some() || !names.insert(full_name.clone()) {
+        return Err(表重复(full_name).into());
       }
     }
-    Ok(())
+    for (raw_table, _) in raw_tables.iter() {
+      self.add_type(Type::Placeholder(config::table_item_type_full_name(
+        &raw_table.get_full_name(),
+      )))?;
+    }
+    for raw_alias in raw_aliases.iter() {
+      self.add_type(Type::Placeholder(raw_alias.full_name.clone()))?;
+    }
+    for raw_alias in raw_aliases.iter() {
+      self.add_alias(&raw_alias.full_name, &raw_alias.raw_type)?;
+    }
+    self.check_alias_cycles()?;
+    let heads = raw_tables
+      .iter()
+      .map(|(raw_table, _)| raw_table.build_head(self))
+      .try_collect::<Vec<_>>()?;
+    let database = &*self;
+    let data = raw_tables
+      .into_par_iter()
+      .zip(heads)
+      .map(|((raw_table, stream), head)| match stream {
+        Some(path) => {
+          let full_name = raw_table.get_full_name();
+          let source = StreamSource::new(path, raw_table.head_rows(), head.0);
+          let key_index = source.check(&full_name, head.1, database)?;
+          let data = Data::new(&full_name, head.1, Content::Stream(source));
+          data.key_index.get_or_init(|| key_index);
+          Ok(data)
+        }
+        None => raw_table.check_data(&head, database),
+      })
+      .collect::<Vec<_>>()
+      .into_iter()
+      .try_collect::<Vec<_>>()?;
+    data.into_iter().map(|data| self.add_data(data)).collect()
   }
 
   pub fn generate_data(&self, target: impl AsRef<Path>) -> Result<()> {
+    self.check_references()?;
     if !target.as_ref().exists() {
       create_dir_all(target.as_ref())?;
     }
-    self.gen_data(target, self.modules.root().id())
+    let mut jobs = Vec::new();
+    self.gen_data(target, self.modules.root().id(), &mut jobs)?;
+    jobs
+      .par_iter()
+      .map(|(json_path, did)| {
+        let data = self.get_data(*did).ok_or(数据不存在)?;
+        // 先写到临时文件, 写完再换掉, 出错时不留下写了一半的 json
+        let tmp_path = json_path.with_extension("json.tmp");
+        let res = File::create(&tmp_path)
+          .map_err(Into::into)
+          .and_then(|file| data.write_json(self, BufWriter::new(file)))
+          .and_then(|()| Ok(rename(&tmp_path, json_path)?));
+        if res.is_err() {
+          let _ = remove_file(&tmp_path);
+        }
+        res
+      })
+      .collect::<Vec<Result<()>>>()
+      .into_iter()
+      .collect()
   }
 
-  fn gen_data(&self, target: impl AsRef<Path>, mid: NodeId) -> Result<()> {
+  /// 先建好文件夹, 收集要写的文件, 之后再一起写
+  fn gen_data(
+    &self,
+    target: impl AsRef<Path>,
+    mid: NodeId,
+    jobs: &mut Vec<(PathBuf, usize)>,
+  ) -> Result<()> {
     for ch in self.modules.get(mid).unwrap().children() {
       let path = target.as_ref().join(&ch.value().name);
       if let Some(did) = ch.value().data {
-        let data = self.get_data(did).ok_or(数据不存在)?;
-        let json = data.build_json(self)?;
-        let json_str = serde_json::to_string(&json)?;
         let json_path = path.with_extension("json");
         dbg!(&json_path);
-        write(json_path, json_str)?;
+        jobs.push((json_path, did));
       } else {
         if !path.exists() {
           create_dir(path.clone())?;
         }
-        self.gen_data(path.clone(), ch.id())?;
+        self.gen_data(path.clone(), ch.id(), jobs)?;
       }
     }
     Ok(())
   }
+}
 
-  pub fn generate_code(&self, target: impl AsRef<Path>) -> Result<()> {
-    let mut csharp = CSharp::try_new(self)?;
-    csharp.generate(target)?;
-    Ok(())
+fn ld_project(
+  root: impl AsRef<Path>,
+  path: impl AsRef<Path>,
+  table_paths: &mut Vec<(PathBuf, String)>,
+  raw_aliases: &mut Vec<RawAlias>,
+) -> Result<()> {
+  let mut ch_paths = read_dir(path.as_ref())?
+    .map(|e| Ok::<_, anyhow::Error>(e?.path()))
+    .try_collect::<Vec<_>>()?;
+  // read_dir 的顺序和文件系统有关, 排序后每次导出的结果才一样
+  ch_paths.sort();
+  // 文件夹内有 .teignore 文件, 则忽略本文件夹
+  if ch_paths
+    .iter()
+    .any(|p| p.is_file() && p.file_name().is_some_and(|f| f == ".teignore"))
+  {
+    return Ok(());
+  }
+
+  for ch_path in ch_paths.iter() {
+    if ch_path.is_file()
+      && ch_path
+        .file_name()
+        .is_some_and(|f| f == config::ALIAS_FILE_NAME)
+    {
+      let full_name =
+        config::os_path_to_path(root.as_ref(), ch_path.clone()).ok_or(文件路径错误)?;
+      raw_aliases.extend(RawAlias::from_csv(
+        ch_path.clone(),
+        config::path_parent(&full_name),
+      )?);
+    } else if ch_path.is_file()
+      && ch_path.extension().is_some()
+      && ch_path.extension().unwrap() == "csv"
+    {
+      let full_name =
+        config::os_path_to_path(root.as_ref(), ch_path.clone()).ok_or(文件路径错误)?;
+      dbg!(&full_name);
+      table_paths.push((ch_path.clone(), full_name));
+    } else if ch_path.is_dir() {
+      ld_project(root.as_ref(), ch_path.clone(), table_paths, raw_aliases)?
+    }
   }
+  Ok(())
 }
 
 pub mod error {
-  use std::backtrace::{self, Backtrace};
-
   use thiserror::Error;
 
   #[derive(Debug, Error)]
   pub enum Error {
     #[error("类型不存在")]
     类型不存在,
-    #[error("类型未知")]
-    类型未知,
-    #[error("类型没有定义")]
-    类型没有定义,
-    #[error("原始数据节点不存在")]
-    原始数据节点不存在,
-    #[error("原始数据节点类型不匹配")]
-    原始数据节点类型不匹配,
-    #[error("数字类型错误")]
-    数字类型错误,
     #[error("数据不存在")]
     数据不存在,
     #[error("文件路径错误")]
     文件路径错误,
-    #[error("原始数据值为空")]
-    原始数据值为空(Backtrace),
+    #[error("类型重名: {0}, 结构体, 别名和表的行类型都不能重名")]
+    类型重名(String),
+    #[error("类型别名循环引用: {0}")]
+    类型别名循环引用(String),
+    #[error("表重复: {0}")]
+    表重复(String),
+    #[error("表名错误: {0}")]
+    表名错误(String),
   }
 }
 
 #[cfg(test)]
 mod test {
-  use crate::basic::database::Database;
+  use crate::basic::{
+    code_generator::Registry,
+    database::{Database, Type},
+    parser,
+    raw_table::RawTable,
+  };
   use anyhow::Result;
-  use std::path::Path;
+  use std::path::{Path, PathBuf};
 
-  const PROJ_PATH: &'static str = "./test/proj/";
-  const JSON_OUT: &'static str = r"D:\Project\gd_读表测试\Gen\Data";
-  const CSHARP_OUT: &'static str = r"D:\Project\gd_读表测试\Gen\Code";
+  const PROJ_PATH: &str = "./test/proj/";
+  const JSON_OUT: &str = r"D:\Project\gd_读表测试\Gen\Data";
+  const CSHARP_OUT: &str = r"D:\Project\gd_读表测试\Gen\Code";
   #[test]
   fn test_load_project() -> Result<()> {
     let mut db = Database::new();
@@ -489,6 +695,170 @@ mod test {
     Ok(())
   }
   #[test]
+  fn test_column_order() -> Result<()> {
+    let mut db = Database::new();
+    db.load_project(PROJ_PATH)?;
+    let data = db.data.iter().find(|d| d.full_name == ".活动").unwrap();
+    let json = serde_json::to_string(&data.build_json().unwrap())?;
+    let keys = ["id", "name", "开始", "结束", "日期", "持续", "冷却"];
+    let pos = keys
+      .iter()
+      .map(|k| json.find(&format!("\"{k}\"")).unwrap())
+      .collect::<Vec<_>>();
+    assert!(pos.is_sorted(), "{json}");
+
+    let mut db2 = Database::new();
+    db2.load_project(PROJ_PATH)?;
+    let data2 = db2.data.iter().find(|d| d.full_name == ".活动").unwrap();
+    assert_eq!(json, serde_json::to_string(&data2.build_json().unwrap())?);
+    Ok(())
+  }
+  #[test]
+  fn test_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Database>();
+  }
+  #[test]
+  fn test_intern_type() -> Result<()> {
+    let mut db = Database::new();
+    let a = parser::parse_raw_type("d<i, l<(i n, fx)>>", ".a", &mut db)?;
+    let count = db.types.len();
+    assert_eq!(
+      parser::parse_raw_type("d< i,l<(i n,fx16.16)> >", ".b", &mut db)?,
+      a
+    );
+    assert_eq!(db.types.len(), count);
+    assert_ne!(
+      parser::parse_raw_type("d<i, l<(i, fx)>>", ".a", &mut db)?,
+      a
+    );
+    assert_eq!(
+      db.type_full_name(a),
+      ".dictionary<.int, .list<.tuple<.int n, .fx16_16>>>"
+    );
+
+    // 名字和加载顺序无关
+    let mut db2 = Database::new();
+    parser::parse_raw_type("l<s>", ".a", &mut db2)?;
+    let b = parser::parse_raw_type("d<i, l<(i n, fx)>>", ".a", &mut db2)?;
+    assert_ne!(a, b);
+    assert_eq!(db.type_full_name(a), db2.type_full_name(b));
+    Ok(())
+  }
+  #[test]
+  fn test_alias() -> Result<()> {
+    let mut db = Database::new();
+    db.add_type(Type::Placeholder(".a.A".to_string()))?;
+    db.add_type(Type::Placeholder(".a.B".to_string()))?;
+    let b = db.add_alias(".a.B", "l<i>")?;
+    let a = db.add_alias(".a.A", "B")?;
+    assert!(db.get_type(db.resolve_alias(a)).unwrap().is_list());
+    assert_eq!(db.resolve_alias(b), db.resolve_alias(a));
+    db.check_alias_cycles()?;
+
+    let mut db = Database::new();
+    db.add_type(Type::Placeholder(".A".to_string()))?;
+    db.add_type(Type::Placeholder(".B".to_string()))?;
+    db.add_alias(".A", "B")?;
+    db.add_alias(".B", "A")?;
+    assert!(db.check_alias_cycles().is_err());
+    Ok(())
+  }
+  #[test]
+  fn test_duplicate_type_name() -> Result<()> {
+    let mut db = Database::new();
+    db.add_type(Type::Placeholder(".A".to_string()))?;
+    db.add_alias(".A", "i")?;
+    let err = db.add_alias(".A", "s").unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "类型重名: .A, 结构体, 别名和表的行类型都不能重名"
+    );
+
+    // 别名和表的行类型同名
+    let mut db = Database::new();
+    db.add_alias(".a.a_item", "i")?;
+    let err = db
+      .add_tables(vec![RawTable::from_grid(
+        ".a",
+        &[vec!["", "id"], vec!["", "i"]],
+      )?])
+      .unwrap_err();
+    assert!(err.to_string().contains("类型重名: .a.a_item"), "{err}");
+    Ok(())
+  }
+  #[test]
+  fn test_type_declared_later() -> Result<()> {
+    // `.a` 的表头用到后面才加的 `.b` 的行类型, 先声明了所有表的类型才能找到
+    let mut db = Database::new();
+    db.add_tables(vec![
+      RawTable::from_grid(
+        ".a",
+        &[
+          vec!["", "id", "b"],
+          vec!["", "i", "l<b.b_item>"],
+          vec!["", "1", ""],
+        ],
+      )?,
+      RawTable::from_grid(".b", &[vec!["", "id"], vec!["", "i"], vec!["", "1"]])?,
+    ])?;
+    let b_item = db.get_type_id_by_full_name(".b.b_item").unwrap();
+    assert!(db.get_type(b_item).unwrap().is_struct());
+
+    // 结构体不能写在一格里
+    let err = Database::new()
+      .add_tables(vec![
+        RawTable::from_grid(
+          ".a",
+          &[
+            vec!["", "id", "b"],
+            vec!["", "i", "b.b_item"],
+            vec!["", "1", r#"{"id": 1}"#],
+          ],
+        )?,
+        RawTable::from_grid(".b", &[vec!["", "id"], vec!["", "i"], vec!["", "1"]])?,
+      ])
+      .unwrap_err();
+    assert!(format!("{err:#}").contains("不支持内联写法"), "{err:#}");
+    Ok(())
+  }
+  /// `test/json_output` 是 `test/proj` 导出的结果, 改了导出格式要一起更新
+  #[test]
+  fn test_json_output() -> Result<()> {
+    fn files(root: &Path, dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+      for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+          files(root, &path, out)?;
+        } else {
+          out.push(path.strip_prefix(root)?.to_path_buf());
+        }
+      }
+      Ok(())
+    }
+    let mut db = Database::new();
+    db.load_project(PROJ_PATH)?;
+    let out = std::env::temp_dir().join("database_test_json_output");
+    let _ = std::fs::remove_dir_all(&out);
+    db.generate_data(&out)?;
+    let expected = Path::new("./test/json_output");
+    let (mut a, mut b) = (Vec::new(), Vec::new());
+    files(expected, expected, &mut a)?;
+    files(&out, &out, &mut b)?;
+    a.sort();
+    b.sort();
+    assert_eq!(a, b);
+    for file in a {
+      assert_eq!(
+        std::fs::read_to_string(expected.join(&file))?,
+        std::fs::read_to_string(out.join(&file))?,
+        "{}",
+        file.display()
+      );
+    }
+    Ok(())
+  }
+  #[test]
   fn test_generate_json() -> Result<()> {
     let mut db = Database::new();
     db.load_project(PROJ_PATH)?;
@@ -500,7 +870,10 @@ mod test {
   fn test_generate_csharp() -> Result<()> {
     let mut db = Database::new();
     db.load_project(PROJ_PATH)?;
-    db.generate_code(CSHARP_OUT)?;
+    db.generate_code(
+      &Registry::with_builtin(),
+      &[("csharp".to_string(), CSHARP_OUT.into())],
+    )?;
     Ok(())
   }
 }
@@ -0,0 +1,287 @@
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat, TimeDelta};
+
+use error::Error::*;
+
+const DATETIME_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M"];
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d"];
+
+const NANOS_PER_TICK: i64 = 100;
+
+/// 统一日期的写法, `2026/10/01` 和 `2026-10-01T10:00` 都当成 `-` 和空格
+fn normalize(s: &str) -> String {
+  s.trim().replace('/', "-").replacen('T', " ", 1)
+}
+
+/// 把结尾的时区拆出来, 没有时区就返回 [None]
+///
+/// 支持 `Z`, `+08:00`, `+0800`, `+08`
+fn split_offset(s: &str) -> Result<(&str, Option<FixedOffset>)> {
+  if let Some(rest) = s.strip_suffix(['Z', 'z']) {
+    return Ok((rest.trim_end(), FixedOffset::east_opt(0)));
+  }
+  // 时区一定在时间部分之后, 跳过日期里的 `-`
+  let Some(pos) = s
+    .char_indices()
+    .skip_while(|&(_, c)| c != ' ')
+    .find(|&(_, c)| c == '+' || c == '-')
+    .map(|(i, _)| i)
+  else {
+    return Ok((s, None));
+  };
+  let (rest, offset) = s.split_at(pos);
+  let sign = if offset.starts_with('-') { -1 } else { 1 };
+  let digits = offset[1..].replace(':', "");
+  let (hours, minutes) = match digits.len() {
+    2 => (&digits[..2], "0"),
+    4 => (&digits[..2], &digits[2..]),
+    _ => return Err(时区格式错误(offset.to_string()).into()),
+  };
+  let hours = hours
+    .parse::<i32>()
+    .map_err(|_| 时区格式错误(offset.to_string()))?;
+  let minutes = minutes
+    .parse::<i32>()
+    .map_err(|_| 时区格式错误(offset.to_string()))?;
+  let offset = FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+    .ok_or_else(|| 时区格式错误(offset.to_string()))?;
+  Ok((rest.trim_end(), Some(offset)))
+}
+
+/// 解析时间点, 没写时区的按 `timezone` 解释
+///
+/// 例: `2026-10-01 10:00`, `2026-10-01T10:00:00+08:00`, `2026/10/01`
+pub fn parse_datetime(s: &str, timezone: FixedOffset) -> Result<DateTime<FixedOffset>> {
+  let normalized = normalize(s);
+  let (local, offset) = split_offset(&normalized)?;
+  let offset = offset.unwrap_or(timezone);
+  let naive = DATETIME_FORMATS
+    .iter()
+    .find_map(|f| NaiveDateTime::parse_from_str(local, f).ok())
+    .or_else(|| {
+      DATE_FORMATS
+        .iter()
+        .find_map(|f| NaiveDate::parse_from_str(local, f).ok())
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+    })
+    .ok_or_else(|| 时间格式错误(s.to_string()))?;
+  naive
+    .and_local_timezone(offset)
+    .single()
+    .ok_or_else(|| 时间格式错误(s.to_string()).into())
+}
+
+pub fn parse_date(s: &str) -> Result<NaiveDate> {
+  let normalized = normalize(s);
+  DATE_FORMATS
+    .iter()
+    .find_map(|f| NaiveDate::parse_from_str(&normalized, f).ok())
+    .ok_or_else(|| 日期格式错误(s.to_string()).into())
+}
+
+/// 解析时长
+///
+/// 例: `1h30m`, `1d 2h`, `1.5h`, `500ms`, `-30s`, `01:30:00`, `1.02:30:00`.
+/// 只写数字的当成秒.
+pub fn parse_duration(s: &str) -> Result<TimeDelta> {
+  let err = || 时长格式错误(s.to_string());
+  let trimmed = s.trim();
+  let (sign, body) = match trimmed.strip_prefix('-') {
+    Some(rest) => (-1, rest.trim_start()),
+    None => (1, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+  };
+  if body.is_empty() {
+    return Err(err().into());
+  }
+  let nanos = if body.contains(':') {
+    parse_clock_duration(body).ok_or_else(err)?
+  } else {
+    parse_unit_duration(body).ok_or_else(err)?
+  };
+  Ok(TimeDelta::nanoseconds(sign * nanos))
+}
+
+/// `[d.]hh:mm[:ss[.fffffff]]`
+fn parse_clock_duration(s: &str) -> Option<i64> {
+  let mut parts = s.split(':');
+  let first = parts.next()?;
+  let (days, hours) = match first.split_once('.') {
+    Some((d, h)) => (d.parse::<i64>().ok()?, h.parse::<i64>().ok()?),
+    None => (0, first.parse::<i64>().ok()?),
+  };
+  let minutes = parts.next()?.parse::<i64>().ok()?;
+  let seconds = match parts.next() {
+    Some(sec) => parse_decimal_nanos(sec, 1_000_000_000)?,
+    None => 0,
+  };
+  if parts.next().is_some() || minutes >= 60 || seconds >= 60_000_000_000 {
+    return None;
+  }
+  let minutes = days
+    .checked_mul(24)?
+    .checked_add(hours)?
+    .checked_mul(60)?
+    .checked_add(minutes)?;
+  minutes.checked_mul(60_000_000_000)?.checked_add(seconds)
+}
+
+/// `1d2h30m10s500ms`, 各部分之间可以有空格
+fn parse_unit_duration(s: &str) -> Option<i64> {
+  let mut total: i64 = 0;
+  let mut rest = s.trim();
+  while !rest.is_empty() {
+    let num_len = rest
+      .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+      .unwrap_or(rest.len());
+    if num_len == 0 {
+      return None;
+    }
+    let (num, tail) = rest.split_at(num_len);
+    let unit_len = tail
+      .find(|c: char| !c.is_ascii_alphabetic())
+      .unwrap_or(tail.len());
+    let (unit, tail) = tail.split_at(unit_len);
+    let unit_nanos = match unit {
+      "d" => 86_400_000_000_000,
+      "h" => 3_600_000_000_000,
+      "m" | "min" => 60_000_000_000,
+      "s" | "" => 1_000_000_000,
+      "ms" => 1_000_000,
+      _ => return None,
+    };
+    // 不带单位的只能单独出现
+    if unit.is_empty() && (total != 0 || !tail.trim().is_empty()) {
+      return None;
+    }
+    total = total.checked_add(parse_decimal_nanos(num, unit_nanos)?)?;
+    rest = tail.trim_start();
+  }
+  Some(total)
+}
+
+/// 按十进制精确计算 `num * unit_nanos`, 不经过浮点
+fn parse_decimal_nanos(num: &str, unit_nanos: i64) -> Option<i64> {
+  let (int, frac) = num.split_once('.').unwrap_or((num, ""));
+  if int.is_empty() && frac.is_empty() {
+    return None;
+  }
+  let int = if int.is_empty() {
+    0
+  } else {
+    int.parse::<i64>().ok()?
+  };
+  let mut value = int.checked_mul(unit_nanos)?;
+  let mut scale = unit_nanos;
+  for c in frac.chars() {
+    let digit = c.to_digit(10)? as i64;
+    if scale % 10 != 0 {
+      // 比纳秒还小的部分直接丢掉
+      break;
+    }
+    scale /= 10;
+    value = value.checked_add(digit * scale)?;
+  }
+  Some(value)
+}
+
+/// 输出成 .net `TimeSpan` 的 `c` 格式: `[-][d.]hh:mm:ss[.fffffff]`
+pub fn format_duration(duration: TimeDelta) -> String {
+  let sign = if duration < TimeDelta::zero() {
+    "-"
+  } else {
+    ""
+  };
+  let abs = duration.abs();
+  let ticks = abs.subsec_nanos() as i64 / NANOS_PER_TICK;
+  let secs = abs.num_seconds();
+  let days = secs / 86_400;
+  let hours = secs / 3_600 % 24;
+  let minutes = secs / 60 % 60;
+  let seconds = secs % 60;
+  let mut res = sign.to_string();
+  if days != 0 {
+    res += &format!("{days}.");
+  }
+  res += &format!("{hours:02}:{minutes:02}:{seconds:02}");
+  if ticks != 0 {
+    res += &format!(".{ticks:07}");
+  }
+  res
+}
+
+pub fn format_datetime(datetime: &DateTime<FixedOffset>) -> String {
+  datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+pub fn format_date(date: &NaiveDate) -> String {
+  date.format("%Y-%m-%d").to_string()
+}
+
+pub mod error {
+  use thiserror::Error;
+
+  #[derive(Debug, Error)]
+  pub enum Error {
+    #[error("时间格式错误: {0}")]
+    时间格式错误(String),
+    #[error("日期格式错误: {0}")]
+    日期格式错误(String),
+    #[error("时长格式错误: {0}")]
+    时长格式错误(String),
+    #[error("时区格式错误: {0}")]
+    时区格式错误(String),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use chrono::FixedOffset;
+
+  use crate::basic::datetime::*;
+
+  #[test]
+  fn test_parse_datetime() {
+    let utc8 = FixedOffset::east_opt(8 * 3600).unwrap();
+    let utc = FixedOffset::east_opt(0).unwrap();
+    let f = |s: &str, tz| format_datetime(&parse_datetime(s, tz).unwrap());
+    assert_eq!(f("2026-10-01 10:00", utc8), "2026-10-01T10:00:00+08:00");
+    assert_eq!(f("2026/10/01 10:00:30", utc), "2026-10-01T10:00:30Z");
+    assert_eq!(
+      f("2026-10-01T10:00:00-05:00", utc8),
+      "2026-10-01T10:00:00-05:00"
+    );
+    assert_eq!(
+      f("2026-10-01 10:00 +0930", utc),
+      "2026-10-01T10:00:00+09:30"
+    );
+    assert_eq!(f("2026-10-01 10:00Z", utc8), "2026-10-01T10:00:00Z");
+    assert_eq!(f("2026-10-01", utc8), "2026-10-01T00:00:00+08:00");
+    assert!(parse_datetime("2026-13-01 10:00", utc).is_err());
+    assert!(parse_datetime("10:00", utc).is_err());
+  }
+
+  #[test]
+  fn test_parse_date() {
+    assert_eq!(
+      format_date(&parse_date(" 2026/1/5 ").unwrap()),
+      "2026-01-05"
+    );
+    assert!(parse_date("2026-10-01 10:00").is_err());
+  }
+
+  #[test]
+  fn test_parse_duration() {
+    let f = |s: &str| format_duration(parse_duration(s).unwrap());
+    assert_eq!(f("1h30m"), "01:30:00");
+    assert_eq!(f("1d 2h"), "1.02:00:00");
+    assert_eq!(f("1.5h"), "01:30:00");
+    assert_eq!(f("500ms"), "00:00:00.5000000");
+    assert_eq!(f("-30s"), "-00:00:30");
+    assert_eq!(f("90"), "00:01:30");
+    assert_eq!(f("1.02:30:00"), "1.02:30:00");
+    assert_eq!(f("00:00:01.25"), "00:00:01.2500000");
+    assert!(parse_duration("1x").is_err());
+    assert!(parse_duration("1h 30").is_err());
+    assert!(parse_duration("").is_err());
+  }
+}
@@ -0,0 +1,275 @@
+//! 用 serde 把表直接读成 rust 的类型, 不用先导出 json
+//!
+//! 时间点, 日期和时间段按导出 json 时的字符串给出, 定点数给原始整数值
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use serde::{
+  Deserialize,
+  de::{self, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, SeqAccess, Visitor},
+  forward_to_deserialize_any,
+};
+
+use crate::basic::{
+  database::Database,
+  datetime,
+  query::{self, Row},
+  value::{Value, ValueKind},
+};
+use error::Error;
+
+impl Database {
+  /// 例: `db.deserialize_table::<HashMap<i32, Item>>(".items")`
+  pub fn deserialize_table<'de, T: Deserialize<'de>>(&'de self, full_name: &str) -> Result<T> {
+    let table = self
+      .table(full_name)
+      .ok_or_else(|| Error::表不存在(full_name.to_string()))?;
+    let value = table
+      .data()
+      .value()
+      .ok_or_else(|| query::error::Error::表没有加载到内存(full_name.to_string()))?;
+    T::deserialize(value)
+      .map_err(|e| anyhow::Error::from(e).context(format!("{full_name} 反序列化失败")))
+  }
+}
+
+impl<'de> Row<'de> {
+  pub fn deserialize<T: Deserialize<'de>>(&self) -> Result<T, Error> {
+    T::deserialize(self.value())
+  }
+}
+
+impl<'de> Deserializer<'de> for &'de Value {
+  type Error = Error;
+
+  fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    let res = match &self.kind {
+      ValueKind::Null => visitor.visit_none(),
+      ValueKind::Int(v) | ValueKind::Fixed(v) => visitor.visit_i64(*v),
+      ValueKind::UInt(v) => visitor.visit_u64(*v),
+      ValueKind::Float(v) => visitor.visit_f64(*v),
+      ValueKind::String(v) => visitor.visit_borrowed_str(v),
+      ValueKind::Bool(v) => visitor.visit_bool(*v),
+      ValueKind::DateTime(v) => visitor.visit_string(datetime::format_datetime(v)),
+      ValueKind::Date(v) => visitor.visit_string(datetime::format_date(v)),
+      ValueKind::Duration(v) => visitor.visit_string(datetime::format_duration(*v)),
+      ValueKind::List(items) | ValueKind::Tuple(items) => visitor.visit_seq(Seq(items.iter())),
+      ValueKind::Map(entries) => visitor.visit_map(Entries {
+        iter: entries.iter(),
+        value: None,
+      }),
+      ValueKind::Struct(fields) => visitor.visit_map(Fields {
+        iter: fields.iter(),
+        value: None,
+      }),
+      // 引用读成主键
+      ValueKind::Ref { key, .. } => return key.as_ref().deserialize_any(visitor),
+    };
+    res.map_err(|e| e.at(self))
+  }
+
+  fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    match &self.kind {
+      ValueKind::Null => visitor.visit_none(),
+      _ => visitor.visit_some(self),
+    }
+  }
+
+  fn deserialize_newtype_struct<V: Visitor<'de>>(
+    self,
+    _name: &'static str,
+    visitor: V,
+  ) -> Result<V::Value, Error> {
+    visitor.visit_newtype_struct(self)
+  }
+
+  /// 只支持没有数据的枚举, 格子里写枚举名
+  fn deserialize_enum<V: Visitor<'de>>(
+    self,
+    _name: &'static str,
+    _variants: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Error> {
+    let res = match self.kind.deref_key() {
+      ValueKind::String(v) => visitor.visit_enum(v.as_str().into_deserializer()),
+      _ => Err(de::Error::invalid_type(
+        de::Unexpected::Other("非字符串"),
+        &visitor,
+      )),
+    };
+    res.map_err(|e: Error| e.at(self))
+  }
+
+  forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+    bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+  }
+}
+
+struct Seq<I>(I);
+
+impl<'de, I: Iterator<Item = &'de Value>> SeqAccess<'de> for Seq<I> {
+  type Error = Error;
+
+  fn next_element_seed<T: DeserializeSeed<'de>>(
+    &mut self,
+    seed: T,
+  ) -> Result<Option<T::Value>, Error> {
+    self.0.next().map(|v| seed.deserialize(v)).transpose()
+  }
+}
+
+/// 字典, 键也是 [Value]
+struct Entries<'de, I> {
+  iter: I,
+  value: Option<&'de Value>,
+}
+
+impl<'de, I: Iterator<Item = &'de (Value, Value)>> MapAccess<'de> for Entries<'de, I> {
+  type Error = Error;
+
+  fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+    let Some((k, v)) = self.iter.next() else {
+      return Ok(None);
+    };
+    self.value = Some(v);
+    seed.deserialize(k).map(Some)
+  }
+
+  fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+    seed.deserialize(self.value.take().expect("先取键再取值"))
+  }
+}
+
+/// 结构体, 键是字段名
+struct Fields<'de, I> {
+  iter: I,
+  value: Option<&'de Value>,
+}
+
+impl<'de, I: Iterator<Item = (&'de Arc<str>, &'de Value)>> MapAccess<'de> for Fields<'de, I> {
+  type Error = Error;
+
+  fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+    let Some((k, v)) = self.iter.next() else {
+      return Ok(None);
+    };
+    self.value = Some(v);
+    seed
+      .deserialize(de::value::BorrowedStrDeserializer::new(k.as_ref()))
+      .map(Some)
+  }
+
+  fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+    seed.deserialize(self.value.take().expect("先取键再取值"))
+  }
+}
+
+pub mod error {
+  use std::fmt::Display;
+
+  use thiserror::Error;
+
+  use crate::basic::value::{Span, Value};
+
+  #[derive(Debug, Error)]
+  pub enum Error {
+    #[error("{0}")]
+    反序列化错误(String),
+    #[error("{span} 的数据错误: {inner}")]
+    带位置 { inner: Box<Error>, span: Span },
+    #[error("表不存在: {0}")]
+    表不存在(String),
+  }
+
+  impl Error {
+    /// 错误第一次经过有位置的值时记下位置, 也就是最里层的格子
+    pub(super) fn at(self, value: &Value) -> Self {
+      match (self, value.span) {
+        (e @ Error::带位置 { .. }, _) | (e, None) => e,
+        (e, Some(span)) => Error::带位置 {
+          inner: Box::new(e),
+          span,
+        },
+      }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+      match self {
+        Error::带位置 { span, .. } => Some(*span),
+        _ => None,
+      }
+    }
+  }
+
+  impl serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+      Error::反序列化错误(msg.to_string())
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::BTreeMap;
+
+  use serde::Deserialize;
+
+  use crate::{HashMap, basic::database::Database};
+  use anyhow::Result;
+
+  #[derive(Debug, Deserialize)]
+  struct Item {
+    id: i32,
+    name: String,
+    #[serde(rename = "技能")]
+    skills: Vec<u32>,
+    #[serde(rename = "男女")]
+    male: bool,
+  }
+
+  #[derive(Debug, Deserialize)]
+  struct Drop {
+    #[serde(rename = "奖励")]
+    reward: (i32, i32),
+    #[serde(rename = "备注")]
+    note: Option<String>,
+  }
+
+  #[test]
+  fn test_deserialize_table() -> Result<()> {
+    let mut db = Database::new();
+    db.load_project("./test/proj/")?;
+    let items = db.deserialize_table::<HashMap<i32, Item>>(".命名空间.a")?;
+    assert_eq!(items[&1].skills, vec![1001, 1002, 1003]);
+    assert!(items[&1].male);
+    assert_eq!(items[&3].id, 3);
+    assert_eq!(items[&3].name, "a");
+
+    let drops = db.deserialize_table::<BTreeMap<i32, Drop>>(".掉落")?;
+    assert_eq!(drops[&2].reward, (1002, 3));
+    assert_eq!(drops[&1].note, None);
+    assert_eq!(drops[&2].note.as_deref(), Some("备注"));
+    Ok(())
+  }
+
+  #[test]
+  fn test_deserialize_error_span() -> Result<()> {
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Bad {
+      age: String,
+    }
+    let mut db = Database::new();
+    db.load_project("./test/proj/")?;
+    let row = db.table(".命名空间.a").unwrap().get(2)?.unwrap();
+    let err = row.deserialize::<Bad>().unwrap_err();
+    assert_eq!(err.span().map(|s| (s.row, s.col)), Some((3, 3)));
+    assert!(
+      db.deserialize_table::<HashMap<i32, Bad>>(".命名空间.a")
+        .is_err()
+    );
+    Ok(())
+  }
+}
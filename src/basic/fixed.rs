@@ -0,0 +1,194 @@
+use anyhow::Result;
+use strum::{Display, EnumString};
+
+use error::Error::*;
+
+/// 不写精度时, `fx` 就是 `fx16.16`
+pub const DEFAULT_INT_BITS: u8 = 16;
+pub const DEFAULT_FRAC_BITS: u8 = 16;
+pub const MAX_BITS: u8 = 64;
+
+/// 十进制转定点数时, 除不尽的部分怎么处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Rounding {
+  /// 四舍五入, 0.5 远离 0
+  #[default]
+  Nearest,
+  /// 银行家舍入, 0.5 取偶数
+  NearestEven,
+  /// 向下取整
+  Floor,
+  /// 向上取整
+  Ceil,
+  /// 向 0 取整
+  TowardZero,
+  /// 不允许舍入, 除不尽就报错
+  Exact,
+}
+
+/// 解析 `fx` 后面的精度, 例: `""`, `"16.16"`, `"48.16"`
+///
+/// 返回 (整数位, 小数位), 整数位包括符号位
+pub fn parse_spec(spec: &str) -> Result<(u8, u8)> {
+  let spec = spec.trim();
+  if spec.is_empty() {
+    return Ok((DEFAULT_INT_BITS, DEFAULT_FRAC_BITS));
+  }
+  let (int_bits, frac_bits) = spec
+    .split_once('.')
+    .ok_or_else(|| 定点数精度错误(spec.to_string()))?;
+  let int_bits = int_bits
+    .trim()
+    .parse::<u8>()
+    .map_err(|_| 定点数精度错误(spec.to_string()))?;
+  let frac_bits = frac_bits
+    .trim()
+    .parse::<u8>()
+    .map_err(|_| 定点数精度错误(spec.to_string()))?;
+  if int_bits == 0 || int_bits as u32 + frac_bits as u32 > MAX_BITS as u32 {
+    return Err(定点数精度错误(spec.to_string()).into());
+  }
+  Ok((int_bits, frac_bits))
+}
+
+/// 小数部分乘 2, 返回溢出到整数位的那一位
+fn double(digits: &mut [u8]) -> u8 {
+  let mut carry = 0;
+  for d in digits.iter_mut().rev() {
+    let v = *d * 2 + carry;
+    *d = v % 10;
+    carry = v / 10;
+  }
+  carry
+}
+
+/// 把十进制写的数按精度转成定点数的原始整数值
+///
+/// 全程用十进制精确计算, 不经过浮点, 结果和平台无关.
+pub fn parse_fixed(s: &str, int_bits: u8, frac_bits: u8, rounding: Rounding) -> Result<i64> {
+  let err = || 定点数格式错误(s.to_string());
+  let trimmed = s.trim();
+  let (negative, body) = match trimmed.strip_prefix('-') {
+    Some(rest) => (true, rest),
+    None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+  };
+  let (int, frac) = body.split_once('.').unwrap_or((body, ""));
+  if (int.is_empty() && frac.is_empty())
+    || !int.chars().all(|c| c.is_ascii_digit())
+    || !frac.chars().all(|c| c.is_ascii_digit())
+  {
+    return Err(err().into());
+  }
+  let overflow = || 定点数超出范围(s.to_string(), int_bits, frac_bits);
+  let int = if int.is_empty() {
+    0
+  } else {
+    int.parse::<u128>().map_err(|_| overflow())?
+  };
+  if int >> (int_bits as u32) != 0 {
+    return Err(overflow().into());
+  }
+
+  let mut digits = frac.bytes().map(|b| b - b'0').collect::<Vec<_>>();
+  let mut bits: u128 = 0;
+  for _ in 0..frac_bits {
+    bits = bits << 1 | double(&mut digits) as u128;
+  }
+  let inexact = digits.iter().any(|&d| d != 0);
+  let half_bit = double(&mut digits) == 1;
+  let above_half = half_bit && digits.iter().any(|&d| d != 0);
+
+  let mut magnitude = int << frac_bits | bits;
+  let round_up = match rounding {
+    Rounding::Exact if inexact => {
+      return Err(定点数无法精确表示(s.to_string(), int_bits, frac_bits).into());
+    }
+    Rounding::Exact | Rounding::TowardZero => false,
+    Rounding::Floor => negative && inexact,
+    Rounding::Ceil => !negative && inexact,
+    Rounding::Nearest => half_bit,
+    Rounding::NearestEven => above_half || (half_bit && magnitude & 1 == 1),
+  };
+  if round_up {
+    magnitude += 1;
+  }
+
+  let total = int_bits as u32 + frac_bits as u32;
+  let limit = 1u128 << (total - 1);
+  let in_range = if negative {
+    magnitude <= limit
+  } else {
+    magnitude < limit
+  };
+  if !in_range {
+    return Err(overflow().into());
+  }
+  let magnitude = magnitude as i128;
+  Ok(if negative { -magnitude } else { magnitude } as i64)
+}
+
+pub mod error {
+  use thiserror::Error;
+
+  #[derive(Debug, Error)]
+  pub enum Error {
+    #[error("定点数精度错误: {0}")]
+    定点数精度错误(String),
+    #[error("定点数格式错误: {0}")]
+    定点数格式错误(String),
+    #[error("定点数超出范围: {0} 不能用 fx{1}.{2} 表示")]
+    定点数超出范围(String, u8, u8),
+    #[error("定点数无法精确表示: {0} 不能用 fx{1}.{2} 精确表示")]
+    定点数无法精确表示(String, u8, u8),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::basic::fixed::*;
+
+  #[test]
+  fn test_parse_spec() {
+    assert_eq!(parse_spec("").unwrap(), (16, 16));
+    assert_eq!(parse_spec("48.16").unwrap(), (48, 16));
+    assert!(parse_spec("0.16").is_err());
+    assert!(parse_spec("48.17").is_err());
+    assert!(parse_spec("16").is_err());
+  }
+
+  #[test]
+  fn test_parse_fixed() {
+    let f = |s: &str, r| parse_fixed(s, 16, 16, r).unwrap();
+    assert_eq!(f("1", Rounding::Exact), 1 << 16);
+    assert_eq!(f("-1.5", Rounding::Exact), -(3 << 15));
+    assert_eq!(f(".25", Rounding::Exact), 1 << 14);
+    assert_eq!(f("0.1", Rounding::TowardZero), 6553);
+    assert_eq!(f("0.1", Rounding::Nearest), 6554);
+    assert_eq!(f("-0.1", Rounding::Floor), -6554);
+    assert_eq!(f("-0.1", Rounding::Ceil), -6553);
+    assert_eq!(f("32767.99999", Rounding::TowardZero), 0x7fff_ffff);
+    assert!(parse_fixed("0.1", 16, 16, Rounding::Exact).is_err());
+    assert!(parse_fixed("32768", 16, 16, Rounding::Nearest).is_err());
+    assert_eq!(
+      parse_fixed("-32768", 16, 16, Rounding::Exact).unwrap(),
+      -(1 << 31)
+    );
+    assert!(parse_fixed("1e3", 16, 16, Rounding::Nearest).is_err());
+  }
+
+  #[test]
+  fn test_parse_fixed_half() {
+    // 1 / 2^3 = 0.125, 用 fx8.2 表示时正好在两个值中间
+    assert_eq!(parse_fixed("0.125", 8, 2, Rounding::Nearest).unwrap(), 1);
+    assert_eq!(
+      parse_fixed("0.125", 8, 2, Rounding::NearestEven).unwrap(),
+      0
+    );
+    assert_eq!(
+      parse_fixed("0.375", 8, 2, Rounding::NearestEven).unwrap(),
+      2
+    );
+    assert_eq!(parse_fixed("-0.125", 8, 2, Rounding::Nearest).unwrap(), -1);
+  }
+}
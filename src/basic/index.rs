@@ -0,0 +1,163 @@
+//! 标注了 `index` 或 `unique` 的列. 生成代码时建二级索引, `unique` 的列导出时检查不能重复
+
+use std::{collections::HashSet, sync::Arc};
+
+use anyhow::Result;
+
+use crate::basic::{
+  database::{Database, Type},
+  value::{Value, ValueKind},
+};
+use error::Error::*;
+
+pub const INDEX_ANNOTATION: &str = "index";
+/// 隐含 `index`
+pub const UNIQUE_ANNOTATION: &str = "unique";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Index {
+  pub field: Arc<str>,
+  /// 一个值只对应一行
+  pub unique: bool,
+}
+
+impl Database {
+  /// 表的行类型里要建索引的列, 按列的顺序
+  pub fn indexes(&self, table_type: usize) -> Vec<Index> {
+    let Some(&Type::Dict(_, item)) = self.get_type(table_type) else {
+      return Vec::new();
+    };
+    let Some(Type::Struct { fields, meta, .. }) = self.get_type(item) else {
+      return Vec::new();
+    };
+    let is_set = |field: &Arc<str>, annotation: &str| {
+      meta
+        .get(field)
+        .is_some_and(|m| m.annotations.get(annotation) == Some(&serde_json::Value::Bool(true)))
+    };
+    fields
+      .keys()
+      .filter_map(|field| {
+        let unique = is_set(field, UNIQUE_ANNOTATION);
+        (unique || is_set(field, INDEX_ANNOTATION)).then(|| Index {
+          field: field.clone(),
+          unique,
+        })
+      })
+      .collect()
+  }
+}
+
+/// 主键和 `unique` 的列都不能重复. 分块导出的表要跨块检查, 所以记住见过的值. 没填的可空列不算
+pub struct UniqueChecker {
+  table: String,
+  keys: HashSet<String>,
+  fields: Vec<(Arc<str>, HashSet<String>)>,
+}
+
+impl UniqueChecker {
+  pub fn new(database: &Database, table_full_name: &str, table_type: usize) -> Self {
+    Self {
+      table: table_full_name.to_string(),
+      keys: HashSet::new(),
+      fields: database
+        .indexes(table_type)
+        .into_iter()
+        .filter(|index| index.unique)
+        .map(|index| (index.field, HashSet::new()))
+        .collect(),
+    }
+  }
+
+  /// `item` 是一行
+  pub fn check(&mut self, item: &Value) -> Result<()> {
+    let ValueKind::Struct(values) = &item.kind else {
+      return Ok(());
+    };
+    for (field, seen) in self.fields.iter_mut() {
+      let Some(value) = values.get(field).filter(|v| !v.kind.is_null()) else {
+        continue;
+      };
+      let json = value.to_json().to_string();
+      if seen.contains(&json) {
+        let position = value.span.map(|s| format!(", 在{s}")).unwrap_or_default();
+        return Err(唯一列重复(self.table.clone(), field.to_string(), json, position).into());
+      }
+      seen.insert(json);
+    }
+    Ok(())
+  }
+
+  /// 表里的一行, 主键和值
+  pub fn check_entry(&mut self, key: &Value, item: &Value) -> Result<()> {
+    let json = key.to_json().to_string();
+    if self.keys.contains(&json) {
+      let position = key.span.map(|s| format!(", 在{s}")).unwrap_or_default();
+      return Err(主键重复(self.table.clone(), json, position).into());
+    }
+    self.keys.insert(json);
+    self.check(item)
+  }
+
+  /// `table` 是整张表
+  pub fn check_table(&mut self, table: &Value) -> Result<()> {
+    if let ValueKind::Map(entries) = &table.kind {
+      for (key, item) in entries {
+        self.check_entry(key, item)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+pub mod error {
+  use thiserror::Error;
+
+  #[derive(Debug, Error)]
+  pub enum Error {
+    #[error("{0} 的主键 {1} 重复{2}")]
+    主键重复(String, String, String),
+    #[error("{0} 的 {1} 列标注了 unique, 值 {2} 重复{3}")]
+    唯一列重复(String, String, String, String),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use anyhow::Result;
+
+  use crate::basic::{database::Database, raw_table::RawTable};
+
+  #[test]
+  fn test_unique() -> Result<()> {
+    fn grid(second: &str) -> Vec<Vec<&str>> {
+      vec![
+        vec!["", "id", "name", "类型"],
+        vec!["", "i", "s?", "i"],
+        vec!["标注", "", "unique", "index"],
+        vec!["", "1", "a", "1"],
+        vec!["", "2", "", "1"],
+        vec!["", "3", second, "2"],
+      ]
+    }
+    let mut db = Database::new();
+    let did = db.add_table(RawTable::from_grid(".物品", &grid(""))?)?;
+    let indexes = db.indexes(db.get_data(did).unwrap().typ);
+    assert_eq!(
+      indexes
+        .iter()
+        .map(|i| (&*i.field, i.unique))
+        .collect::<Vec<_>>(),
+      [("name", true), ("类型", false)]
+    );
+
+    let err = Database::new()
+      .add_table(RawTable::from_grid(".物品", &grid("a"))?)
+      .unwrap_err();
+    assert_eq!(
+      format!("{err:#}"),
+      ".物品 的 name 列标注了 unique, 值 \"a\" 重复, 在第 6 行第 3 列"
+    );
+    Ok(())
+  }
+}
@@ -1,22 +1,27 @@
-use std::{ops::Not, rc::Rc};
+use std::sync::Arc;
 
 use anyhow::Result;
-use ego_tree::{NodeId, Tree};
-use strum::{EnumIs, EnumTryAs};
+use ego_tree::Tree;
 
 use crate::{
-  HashMap,
+  IndexMap,
   basic::{
     config,
-    database::{self, Database, ItemTag, RawData, Type},
+    database::{Database, FieldMeta, RawData, Type},
+    fixed,
     raw_table::{Cell, RawTable},
+    type_expr::{self, TypeArg, TypeExpr, TypeExprKind},
   },
 };
 
+#[derive(Debug)]
 pub struct Column {
-  pub field: Rc<String>,
+  pub field: Arc<str>,
   pub typ: usize,
+  /// 格子空着时用这个
+  pub default: Option<Cell>,
 }
+#[derive(Debug, Default)]
 pub struct Parser {
   pub columns: Vec<Column>,
 }
@@ -28,9 +33,10 @@ impl Parser {
     }
   }
   pub fn parse_head(&mut self, raw_table: &RawTable, database: &mut Database) -> Result<usize> {
-    let mut fields = HashMap::new();
+    let mut fields = IndexMap::new();
+    let mut meta = IndexMap::new();
     let head_area = raw_table.get_head_area();
-    let mut last_field: Option<Rc<String>> = None;
+    let mut last_field: Option<Arc<str>> = None;
     let mut last_type = None;
     for c in 0..head_area.shape()[1] {
       let raw_field = head_area.get([0, c]).unwrap();
@@ -41,23 +47,39 @@ impl Parser {
         field = last_field.clone().unwrap();
         typ = last_type.unwrap();
       } else {
-        field = Rc::new(raw_field.trim().to_string());
+        field = Arc::from(raw_field.trim());
         last_field = Some(field.clone());
-        typ = parse_raw_type(raw_type, database)?;
-        last_type = Some(typ.clone());
-        fields.insert(field.clone(), typ.clone());
+        typ = parse_raw_type(raw_type, &raw_table.get_full_name(), database).map_err(|e| {
+          e.context(format!(
+            "{} 的 {} 列类型错误",
+            raw_table.get_full_name(),
+            raw_field.trim()
+          ))
+        })?;
+        last_type = Some(typ);
+        fields.insert(field.clone(), typ);
+        let field_meta = parse_field_meta(raw_table, c)?;
+        if !field_meta.is_empty() {
+          meta.insert(field.clone(), field_meta);
+        }
       }
+      let default = raw_table
+        .get_head_extra(config::DEFAULT_ROW_MARK, c)
+        .filter(|s| !s.trim().is_empty())
+        .cloned();
       self.columns.push(Column {
         field: field.clone(),
-        typ: typ.clone(),
+        typ,
+        default,
       });
     }
     let item = Type::Struct {
       full_name: config::table_item_type_full_name(&raw_table.get_full_name()),
       fields,
+      meta,
     };
-    let iid = database.add_type(item);
-    let did = database.add_type(Type::Dict(self.columns[0].typ, iid));
+    let iid = database.add_type(item)?;
+    let did = database.add_type(Type::Dict(self.columns[0].typ, iid))?;
     Ok(did)
   }
   pub fn parse_data(&self, raw_table: &RawTable, database: &Database) -> Result<Tree<RawData>> {
@@ -65,15 +87,23 @@ impl Parser {
     let mut data_tree = Tree::new(RawData::Many);
     for row in 0..data_area.shape()[0] {
       let mut root_mut = data_tree.root_mut();
-      let mut entry_id = root_mut.append(RawData::Many).id();
+      let entry_id = root_mut.append(RawData::Many).id();
       let item_id = data_tree
         .get_mut(entry_id)
         .unwrap()
-        .append(RawData::Struct(HashMap::new()))
+        .append(RawData::Struct(IndexMap::new()))
         .id();
       for col in 0..data_area.shape()[1] {
         let cell = data_area.get([row, col]).unwrap();
+        let cell = match &self.columns[col].default {
+          Some(default) if cell.trim().is_empty() => default,
+          _ => cell,
+        };
+        let span = raw_table.data_span(row, col);
         let field = &self.columns[col].field;
+        let typ = database
+          .get_type(database.resolve_alias(self.columns[col].typ))
+          .ok_or(error::Error::类型不存在)?;
         if let Some(id) = data_tree
           .get(item_id)
           .unwrap()
@@ -82,30 +112,28 @@ impl Parser {
           .unwrap()
           .get(field)
         {
-          if !cell.is_empty() {
+          // 元组按位置取值, 空格子也要占位
+          if typ.is_tuple() || !cell.is_empty() {
             data_tree
               .get_mut(*id)
               .ok_or(error::Error::Map存储了无效子节点)?
-              .append(RawData::One(cell.clone()));
+              .append(RawData::One(cell.clone(), span));
           }
         } else {
-          let typ = database
-            .get_type(self.columns[col].typ)
-            .ok_or(error::Error::类型不存在)?;
           assert!(!typ.is_placeholder());
           let id;
-          if typ.is_list() {
+          if typ.is_list() || typ.is_tuple() {
             let mut item = data_tree.get_mut(item_id).unwrap();
             let mut arr = item.append(RawData::Many);
             id = arr.id();
-            if !cell.is_empty() {
-              arr.append(RawData::One(cell.clone())).id();
+            if typ.is_tuple() || !cell.is_empty() {
+              arr.append(RawData::One(cell.clone(), span)).id();
             }
           } else {
             id = data_tree
               .get_mut(item_id)
               .unwrap()
-              .append(RawData::One(cell.clone()))
+              .append(RawData::One(cell.clone(), span))
               .id();
           }
           data_tree
@@ -134,64 +162,111 @@ impl Parser {
   }
 }
 
-pub fn parse_raw_type(raw_type: &str, database: &mut Database) -> Result<usize> {
-  let raw_type = raw_type.trim();
-  let tid = match raw_type {
-    "i" | "f" | "s" | "b" => database
-      .get_type_id_by_full_name(&config::path_rel_to_global(
-        config::BUILTIN_TYPE_NAMES.get(raw_type).unwrap(),
-      ))
-      .unwrap(),
-    _ => {
-      let a = raw_type
-        .chars()
-        .next()
-        .ok_or(error::Error::类型声明语法错误)?;
-      match a {
-        'l' => {
-          let follow = raw_type[1..].trim();
-          if follow.starts_with(config::TYPE_PARAMETER_DELIMINATOR_LEFT)
-            && follow.ends_with(config::TYPE_PARAMETER_DELIMINATOR_RIGHT)
-          {
-            let mut pars = follow[1..follow.len() - 1].split(config::TYPE_PARAMETER_SPLITOR);
-            let p1 = pars.next().ok_or(error::Error::类型声明语法错误)?;
-            if let Some(p) = pars.next() {
-              if !p.trim().is_empty() {
-                return Err(error::Error::类型声明语法错误.into());
-              } else if pars.next().is_some() {
-                return Err(error::Error::类型声明语法错误.into());
-              }
-            };
-            let pid = parse_raw_type(p1, database)?;
-            database.add_type(Type::List(pid))
-          } else {
-            return Err(error::Error::类型声明语法错误.into());
-          }
+/// 一列的描述, 标注和默认值, 列表和元组占的后面几列不算
+fn parse_field_meta(raw_table: &RawTable, col: usize) -> Result<FieldMeta> {
+  let extra = |mark| {
+    raw_table
+      .get_head_extra(mark, col)
+      .map(|s| s.trim())
+      .filter(|s| !s.is_empty())
+  };
+  Ok(FieldMeta {
+    description: extra(config::DESCRIPTION_ROW_MARK).map(str::to_string),
+    annotations: extra(config::ANNOTATION_ROW_MARK)
+      .map(parse_annotations)
+      .transpose()?
+      .unwrap_or_default(),
+    default: extra(config::DEFAULT_ROW_MARK).map(str::to_string),
+  })
+}
+
+/// `index, ref=.items` 拆成 `index: true`, `ref: ".items"`.
+/// 值里有 `,` 时用引号括起来, 例: `desc="a, b"`
+pub fn parse_annotations(s: &str) -> Result<serde_json::Map<String, serde_json::Value>> {
+  split_literal(s)?
+    .into_iter()
+    .filter(|a| !a.is_empty())
+    .map(|a| match a.split_once('=') {
+      Some((k, v)) => {
+        let v = v.trim();
+        let v = if v.starts_with('"') {
+          serde_json::from_str::<String>(v)
+            .map_err(|_| error::Error::标注的值错误(a.to_string()))?
+        } else {
+          v.to_string()
+        };
+        Ok((k.trim().to_string(), serde_json::Value::from(v)))
+      }
+      None => Ok((a.to_string(), serde_json::Value::Bool(true))),
+    })
+    .collect()
+}
+
+/// 解析类型声明, 自定义类型的名字先在 `module` 里找, 找不到再依次去上层模块找
+pub fn parse_raw_type(raw_type: &str, module: &str, database: &mut Database) -> Result<usize> {
+  let expr = type_expr::parse(raw_type)?;
+  build_type(&expr, raw_type, module, database)
+}
+
+fn build_type(expr: &TypeExpr, text: &str, module: &str, database: &mut Database) -> Result<usize> {
+  let err = |message: String| type_expr::error(text, expr.span.start, message);
+  let tid = match &expr.kind {
+    TypeExprKind::Optional(inner) => {
+      if matches!(inner.kind, TypeExprKind::Optional(_)) {
+        return Err(err("重复的 `?`".to_string()).into());
+      }
+      let tid = build_type(inner, text, module, database)?;
+      database.add_type(Type::Optional(tid))?
+    }
+    TypeExprKind::Tuple(items) => build_tuple(items, text, module, database)?,
+    TypeExprKind::Named { path, args } => {
+      let no_names = |count: usize| {
+        if args.len() != count {
+          Err(err(format!("`{path}` 需要 {count} 个类型参数")))
+        } else if args.iter().any(|a| a.name.is_some()) {
+          Err(err(format!("`{path}` 的类型参数不能有名字")))
+        } else {
+          Ok(())
         }
-        'd' => {
-          let follow = raw_type[1..].trim();
-          if follow.starts_with(config::TYPE_PARAMETER_DELIMINATOR_LEFT)
-            && follow.ends_with(config::TYPE_PARAMETER_DELIMINATOR_RIGHT)
-          {
-            let mut pars = follow[1..follow.len() - 1].split(config::TYPE_PARAMETER_SPLITOR);
-            let p1 = pars.next().ok_or(error::Error::类型声明语法错误)?;
-            let p2 = pars.next().ok_or(error::Error::类型声明语法错误)?;
-            if let Some(p) = pars.next() {
-              if !p.trim().is_empty() {
-                return Err(error::Error::类型声明语法错误.into());
-              } else if pars.next().is_some() {
-                return Err(error::Error::类型声明语法错误.into());
-              }
-            };
-            let p1id = parse_raw_type(&p1, database)?;
-            let p2id = parse_raw_type(&p2, database)?;
-            database.add_type(Type::Dict(p1id, p2id))
-          } else {
-            return Err(error::Error::类型声明语法错误.into());
-          }
+      };
+      match path.as_str() {
+        name if let Some(builtin) = config::BUILTIN_TYPE_NAMES.get(name) => {
+          no_names(0)?;
+          database
+            .get_type_id_by_full_name(&config::path_rel_to_global(builtin))
+            .unwrap()
+        }
+        "l" => {
+          no_names(1)?;
+          let pid = build_type(&args[0].typ, text, module, database)?;
+          database.add_type(Type::List(pid))?
+        }
+        "d" => {
+          no_names(2)?;
+          let p1id = build_type(&args[0].typ, text, module, database)?;
+          let p2id = build_type(&args[1].typ, text, module, database)?;
+          database.add_type(Type::Dict(p1id, p2id))?
+        }
+        "t" => build_tuple(args, text, module, database)?,
+        _ if path.starts_with(config::FIXED_TYPE_PREFIX)
+          && path[config::FIXED_TYPE_PREFIX.len()..]
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.') =>
+        {
+          no_names(0)?;
+          let (int_bits, frac_bits) = fixed::parse_spec(&path[config::FIXED_TYPE_PREFIX.len()..])
+            .map_err(|e| err(e.to_string()))?;
+          database.add_type(Type::Fixed {
+            int_bits,
+            frac_bits,
+          })?
         }
         _ => {
-          unimplemented!("目前不支持自定义类型")
+          if !args.is_empty() {
+            return Err(err(format!("`{path}` 不是泛型类型")).into());
+          }
+          resolve_type_name(path, module, database)
+            .ok_or_else(|| err(format!("类型 `{path}` 不存在")))?
         }
       }
     }
@@ -199,6 +274,124 @@ pub fn parse_raw_type(raw_type: &str, database: &mut Database) -> Result<usize>
   Ok(tid)
 }
 
+fn build_tuple(
+  items: &[TypeArg],
+  text: &str,
+  module: &str,
+  database: &mut Database,
+) -> Result<usize> {
+  if items.is_empty() {
+    return Err(error::Error::元组不能为空.into());
+  }
+  if items.len() > config::MAX_TUPLE_LEN {
+    return Err(error::Error::元组元素过多(items.len()).into());
+  }
+  let mut ids = Vec::new();
+  let mut names = Vec::new();
+  for item in items {
+    ids.push(build_type(&item.typ, text, module, database)?);
+    names.push(item.name.clone());
+  }
+  database.add_type(Type::Tuple { items: ids, names })
+}
+
+/// `.a.b` 是全名, 直接找. `a.b` 从 `module` 开始一层层往上找
+pub fn resolve_type_name(name: &str, module: &str, database: &Database) -> Option<usize> {
+  if name.starts_with(config::PATH_SPLITOR) {
+    return database.get_type_id_by_full_name(name);
+  }
+  let mut module = module;
+  loop {
+    let full_name = config::path_join(&[module, name]);
+    if let Some(id) = database.get_type_id_by_full_name(&full_name) {
+      return Some(id);
+    }
+    if module.is_empty() {
+      return None;
+    }
+    module = config::path_parent(module);
+  }
+}
+
+/// 把一个格子里的字面量按 `,` 拆开, 会跳过括号和引号里面的 `,`
+///
+/// 最外层的 `()` 或 `[]` 可以省略, 例: `(1, "a,b")`, `[1, 2]`, `1, 2`, `(1, 2), (3, 4)`
+pub fn split_literal(s: &str) -> Result<Vec<&str>> {
+  let mut s = s.trim();
+  if is_wrapped(s) {
+    s = &s[1..s.len() - 1];
+  }
+  let mut parts = Vec::new();
+  let mut scanner = LiteralScanner::default();
+  let mut start = 0;
+  for (i, c) in s.char_indices() {
+    let depth = scanner.feed(c)?;
+    if c == ',' && depth == Some(0) {
+      parts.push(s[start..i].trim());
+      start = i + 1;
+    }
+  }
+  if scanner.depth != 0 || scanner.in_quote {
+    return Err(error::Error::字面量括号不匹配.into());
+  }
+  parts.push(s[start..].trim());
+  Ok(parts)
+}
+
+/// 开头的 `(` 或 `[` 和最后一个字符配对
+fn is_wrapped(s: &str) -> bool {
+  if !s.starts_with(['(', '[']) {
+    return false;
+  }
+  let mut scanner = LiteralScanner::default();
+  for (i, c) in s.char_indices() {
+    match scanner.feed(c) {
+      Ok(Some(0)) => return i + c.len_utf8() == s.len(),
+      Ok(_) => {}
+      Err(_) => return false,
+    }
+  }
+  false
+}
+
+/// 逐个字符记下括号深度和是否在引号里
+#[derive(Default)]
+struct LiteralScanner {
+  depth: usize,
+  in_quote: bool,
+  escaped: bool,
+}
+
+impl LiteralScanner {
+  /// 读入一个字符, 返回读完之后的括号深度; 引号里的字符返回 None
+  fn feed(&mut self, c: char) -> Result<Option<usize>> {
+    if self.in_quote {
+      match c {
+        _ if self.escaped => self.escaped = false,
+        '\\' => self.escaped = true,
+        '"' => self.in_quote = false,
+        _ => {}
+      }
+      return Ok(None);
+    }
+    match c {
+      '"' => {
+        self.in_quote = true;
+        return Ok(None);
+      }
+      '(' | '[' => self.depth += 1,
+      ')' | ']' => {
+        self.depth = self
+          .depth
+          .checked_sub(1)
+          .ok_or(error::Error::字面量括号不匹配)?
+      }
+      _ => {}
+    }
+    Ok(Some(self.depth))
+  }
+}
+
 pub mod error {
   use thiserror::Error;
 
@@ -208,7 +401,96 @@ pub mod error {
     Map存储了无效子节点,
     #[error("类型不存在")]
     类型不存在,
-    #[error("类型声明语法错误")]
-    类型声明语法错误,
+    #[error("元组不能为空")]
+    元组不能为空,
+    #[error("标注的值错误: {0}")]
+    标注的值错误(String),
+    #[error("元组元素过多: {0}")]
+    元组元素过多(usize),
+    #[error("字面量括号不匹配")]
+    字面量括号不匹配,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::basic::{
+    database::{Database, Type},
+    parser::{parse_annotations, parse_raw_type, split_literal},
+  };
+  use anyhow::Result;
+
+  #[test]
+  fn test_parse_raw_type() -> Result<()> {
+    let mut db = Database::new();
+    let tid = parse_raw_type("d<i, d<s, l<i?>>>", ".x", &mut db)?;
+    let &Type::Dict(k, v) = db.get_type(tid).unwrap() else {
+      panic!()
+    };
+    assert!(db.get_type(k).unwrap().is_int());
+    let &Type::Dict(_, v) = db.get_type(v).unwrap() else {
+      panic!()
+    };
+    let &Type::List(v) = db.get_type(v).unwrap() else {
+      panic!()
+    };
+    assert!(db.get_type(v).unwrap().is_optional());
+    Ok(())
+  }
+
+  #[test]
+  fn test_parse_raw_type_named() -> Result<()> {
+    let mut db = Database::new();
+    let id = db.add_type(Type::Placeholder(".命名空间.a.a_item".to_string()))?;
+    assert_eq!(parse_raw_type("a.a_item", ".命名空间.a2", &mut db)?, id);
+    assert_eq!(parse_raw_type(".命名空间.a.a_item", ".x", &mut db)?, id);
+    let err = parse_raw_type("l<a.a_item>", ".x", &mut db).unwrap_err();
+    assert!(err.to_string().contains("第 3 个字符"), "{err}");
+    Ok(())
+  }
+
+  #[test]
+  fn test_parse_tuple() -> Result<()> {
+    let mut db = Database::new();
+    assert!(
+      parse_raw_type("t", ".x", &mut db)
+        .unwrap_err()
+        .to_string()
+        .contains("元组不能为空")
+    );
+    assert!(parse_raw_type("t<>", ".x", &mut db).is_err());
+    assert!(parse_raw_type("()", ".x", &mut db).is_err());
+    let tid = parse_raw_type("(i)", ".x", &mut db)?;
+    assert_eq!(parse_raw_type("t<i>", ".x", &mut db)?, tid);
+    Ok(())
+  }
+
+  #[test]
+  fn test_split_literal() {
+    assert_eq!(split_literal("(1, 2)").unwrap(), vec!["1", "2"]);
+    assert_eq!(split_literal("1,2").unwrap(), vec!["1", "2"]);
+    assert_eq!(
+      split_literal(r#"[(1, 2), "a,\"b"]"#).unwrap(),
+      vec!["(1, 2)", r#""a,\"b""#]
+    );
+    assert!(split_literal("(1, (2)").is_err());
+    // 外层括号省略时, 开头和结尾的括号不是一对
+    assert_eq!(
+      split_literal("(1,2),(3,4)").unwrap(),
+      vec!["(1,2)", "(3,4)"]
+    );
+    assert_eq!(split_literal("[1],[2]").unwrap(), vec!["[1]", "[2]"]);
+    assert_eq!(split_literal(r#"("a)", 1)"#).unwrap(), vec![r#""a)""#, "1"]);
+  }
+
+  #[test]
+  fn test_parse_annotations() -> Result<()> {
+    let annotations = parse_annotations(r#"index, desc="a, b", ref=.items"#)?;
+    assert_eq!(annotations.len(), 3);
+    assert_eq!(annotations["index"], true);
+    assert_eq!(annotations["desc"], "a, b");
+    assert_eq!(annotations["ref"], ".items");
+    assert!(parse_annotations(r#"desc="a"#).is_err());
+    Ok(())
   }
 }
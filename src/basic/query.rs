@@ -0,0 +1,319 @@
+//! 加载之后在 rust 里直接读表, 不用经过 json
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeDelta};
+
+use crate::{
+  HashMap,
+  basic::{
+    database::{Data, Database, Type},
+    value::{Value, ValueKind},
+  },
+};
+use error::Error::*;
+
+/// 能当主键查询的值. 主键是别的类型的表只能遍历
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+  Int(i64),
+  UInt(u64),
+  String(String),
+  Bool(bool),
+}
+
+impl Key {
+  pub fn from_value(value: &Value) -> Option<Self> {
+    match value.kind.deref_key() {
+      ValueKind::Int(v) => Some(Key::Int(*v)),
+      ValueKind::UInt(v) => Some(Key::UInt(*v)),
+      ValueKind::String(v) => Some(Key::String(v.clone())),
+      ValueKind::Bool(v) => Some(Key::Bool(*v)),
+      _ => None,
+    }
+  }
+}
+
+impl From<i64> for Key {
+  fn from(value: i64) -> Self {
+    Key::Int(value)
+  }
+}
+impl From<i32> for Key {
+  fn from(value: i32) -> Self {
+    Key::Int(value as i64)
+  }
+}
+impl From<u64> for Key {
+  fn from(value: u64) -> Self {
+    i64::try_from(value).map_or(Key::UInt(value), Key::Int)
+  }
+}
+impl From<&str> for Key {
+  fn from(value: &str) -> Self {
+    Key::String(value.to_string())
+  }
+}
+impl From<String> for Key {
+  fn from(value: String) -> Self {
+    Key::String(value)
+  }
+}
+impl From<bool> for Key {
+  fn from(value: bool) -> Self {
+    Key::Bool(value)
+  }
+}
+
+/// 主键到行号, 第一次查询时建立
+pub(crate) fn build_key_index(value: &Value) -> HashMap<Key, usize> {
+  let mut index = HashMap::new();
+  if let ValueKind::Map(entries) = &value.kind {
+    for (i, (k, _)) in entries.iter().enumerate() {
+      if let Some(k) = Key::from_value(k) {
+        // 加载时已经检查过主键不重复
+        index.insert(k, i);
+      }
+    }
+  }
+  index
+}
+
+impl Database {
+  /// 所有的表, 按模块的顺序
+  pub fn tables(&self) -> impl Iterator<Item = Table<'_>> {
+    self
+      .modules
+      .root()
+      .descendants()
+      .filter_map(|m| m.value().data)
+      .map(|did| Table {
+        database: self,
+        data: &self.data[did],
+      })
+  }
+
+  /// `full_name` 例: `.命名空间.a`
+  pub fn table(&self, full_name: &str) -> Option<Table<'_>> {
+    let mid = self.get_module(full_name)?;
+    let did = self.modules.get(mid)?.value().data?;
+    Some(Table {
+      database: self,
+      data: &self.data[did],
+    })
+  }
+}
+
+#[derive(Clone, Copy)]
+pub struct Table<'a> {
+  database: &'a Database,
+  data: &'a Data,
+}
+
+impl<'a> Table<'a> {
+  pub fn full_name(&self) -> &'a str {
+    &self.data.full_name
+  }
+
+  pub fn data(&self) -> &'a Data {
+    self.data
+  }
+
+  /// 表的类型是 `d<主键, 行>`, 返回 (主键类型, 行类型)
+  fn key_item_type(&self) -> (usize, usize) {
+    match self.database.get_type(self.data.typ) {
+      Some(&Type::Dict(key, item)) => (key, item),
+      _ => unreachable!("表的类型一定是字典"),
+    }
+  }
+
+  pub fn key_type(&self) -> usize {
+    self.key_item_type().0
+  }
+
+  pub fn item_type(&self) -> usize {
+    self.key_item_type().1
+  }
+
+  /// 字段名和字段类型, 按列的顺序
+  pub fn fields(&self) -> Vec<(&'a str, usize)> {
+    match self.database.get_type(self.item_type()) {
+      Some(Type::Struct { fields, .. }) => fields.iter().map(|(k, v)| (k.as_ref(), *v)).collect(),
+      _ => Vec::new(),
+    }
+  }
+
+  fn entries(&self) -> Result<&'a [(Value, Value)]> {
+    let value = self
+      .data
+      .value()
+      .ok_or_else(|| 表没有加载到内存(self.data.full_name.clone()))?;
+    match &value.kind {
+      ValueKind::Map(entries) => Ok(entries),
+      _ => unreachable!("表的值一定是字典"),
+    }
+  }
+
+  pub fn len(&self) -> Result<usize> {
+    Ok(self.entries()?.len())
+  }
+
+  pub fn is_empty(&self) -> Result<bool> {
+    Ok(self.entries()?.is_empty())
+  }
+
+  /// 按表里的顺序
+  pub fn rows(&self) -> Result<impl Iterator<Item = Row<'a>> + use<'a>> {
+    Ok(
+      self
+        .entries()?
+        .iter()
+        .map(|(key, value)| Row { key, value }),
+    )
+  }
+
+  pub fn get(&self, key: impl Into<Key>) -> Result<Option<Row<'a>>> {
+    let entries = self.entries()?;
+    let index = self.data.key_index();
+    Ok(index.get(&key.into()).map(|&i| Row {
+      key: &entries[i].0,
+      value: &entries[i].1,
+    }))
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Row<'a> {
+  key: &'a Value,
+  value: &'a Value,
+}
+
+impl<'a> Row<'a> {
+  pub fn key(&self) -> &'a Value {
+    self.key
+  }
+
+  pub fn value(&self) -> &'a Value {
+    self.value
+  }
+
+  pub fn get(&self, field: &str) -> Result<&'a Value> {
+    match &self.value.kind {
+      ValueKind::Struct(fields) => fields
+        .get(field)
+        .ok_or_else(|| 字段不存在(field.to_string()).into()),
+      _ => unreachable!("表的行一定是结构体"),
+    }
+  }
+
+  fn get_as<T>(&self, field: &str, f: impl FnOnce(&'a ValueKind) -> Option<T>) -> Result<T> {
+    let value = self.get(field)?;
+    f(value.kind.deref_key()).ok_or_else(|| {
+      let e = anyhow::Error::from(字段类型不匹配(field.to_string()));
+      match value.span {
+        Some(span) => e.context(format!("{span}")),
+        None => e,
+      }
+    })
+  }
+
+  pub fn get_int(&self, field: &str) -> Result<i64> {
+    self.get_as(field, |v| v.try_as_int_ref().copied())
+  }
+  /// 不是负数的整数, 包括超过 [i64::MAX] 的
+  pub fn get_uint(&self, field: &str) -> Result<u64> {
+    self.get_as(field, |v| match v {
+      ValueKind::Int(v) => u64::try_from(*v).ok(),
+      ValueKind::UInt(v) => Some(*v),
+      _ => None,
+    })
+  }
+  pub fn get_float(&self, field: &str) -> Result<f64> {
+    self.get_as(field, |v| v.try_as_float_ref().copied())
+  }
+  pub fn get_str(&self, field: &str) -> Result<&'a str> {
+    self.get_as(field, |v| v.try_as_string_ref().map(String::as_str))
+  }
+  pub fn get_bool(&self, field: &str) -> Result<bool> {
+    self.get_as(field, |v| v.try_as_bool_ref().copied())
+  }
+  pub fn get_datetime(&self, field: &str) -> Result<DateTime<FixedOffset>> {
+    self.get_as(field, |v| v.try_as_date_time_ref().copied())
+  }
+  pub fn get_date(&self, field: &str) -> Result<NaiveDate> {
+    self.get_as(field, |v| v.try_as_date_ref().copied())
+  }
+  pub fn get_duration(&self, field: &str) -> Result<TimeDelta> {
+    self.get_as(field, |v| v.try_as_duration_ref().copied())
+  }
+  /// 定点数的原始整数值
+  pub fn get_fixed(&self, field: &str) -> Result<i64> {
+    self.get_as(field, |v| v.try_as_fixed_ref().copied())
+  }
+  /// 列表和元组都可以
+  pub fn get_list(&self, field: &str) -> Result<&'a [Value]> {
+    self.get_as(field, |v| match v {
+      ValueKind::List(items) | ValueKind::Tuple(items) => Some(items.as_slice()),
+      _ => None,
+    })
+  }
+  /// 可以不填的字段, 没填时返回 [None]
+  pub fn get_optional(&self, field: &str) -> Result<Option<&'a Value>> {
+    let value = self.get(field)?;
+    Ok((!value.kind.is_null()).then_some(value))
+  }
+}
+
+pub mod error {
+  use thiserror::Error;
+
+  #[derive(Debug, Error)]
+  pub enum Error {
+    #[error("表没有加载到内存: {0}")]
+    表没有加载到内存(String),
+    #[error("字段不存在: {0}")]
+    字段不存在(String),
+    #[error("字段类型不匹配: {0}")]
+    字段类型不匹配(String),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::basic::database::Database;
+  use anyhow::Result;
+
+  #[test]
+  fn test_query() -> Result<()> {
+    let mut db = Database::new();
+    db.load_project("./test/proj/")?;
+    let names = db.tables().map(|t| t.full_name()).collect::<Vec<_>>();
+    assert!(names.contains(&".命名空间.a2"), "{names:?}");
+
+    let table = db.table(".命名空间.a").unwrap();
+    let fields = table.fields();
+    assert_eq!(fields[0].0, "id");
+    assert_eq!(db.type_full_name(fields[5].1), ".list<.int>");
+    assert_eq!(table.len()?, 3);
+
+    let row = table.get(2)?.unwrap();
+    assert_eq!(row.get_int("age")?, 21);
+    assert_eq!(row.get_str("name")?, "a");
+    assert!(!row.get_bool("男女")?);
+    assert!(row.get_list("技能")?.is_empty());
+    assert!(row.get_str("age").is_err());
+    assert!(row.get("不存在").is_err());
+    assert!(table.get(4)?.is_none());
+
+    let ids = table
+      .rows()?
+      .map(|r| r.get_int("id"))
+      .try_collect::<Vec<_>>()?;
+    assert_eq!(ids, vec![1, 2, 3]);
+
+    let row = db.table(".掉落").unwrap().get(2)?.unwrap();
+    assert!(row.get_optional("备注")?.is_some());
+    let row = db.table(".掉落").unwrap().get(1)?.unwrap();
+    assert!(row.get_optional("备注")?.is_none());
+    Ok(())
+  }
+}
@@ -1,20 +1,24 @@
 use anyhow::Result;
-use csv::ReaderBuilder;
-use std::{
-  ops::RangeBounds,
-  path::{Path, PathBuf},
-  rc::Rc,
-};
+use csv::{ReaderBuilder, StringRecord};
+use std::{path::Path, sync::Arc};
 
-use error::*;
 use ndarray::{Array2, ArrayView2, s};
 
-use crate::basic::{
-  database::{self, Data, Database},
-  parser::Parser,
+use crate::{
+  HashSet,
+  basic::{
+    config,
+    database::{Content, Data, Database},
+    index::UniqueChecker,
+    parser::Parser,
+    value::{self, Span},
+  },
 };
 
-pub type Cell = Rc<String>;
+pub type Cell = Arc<str>;
+
+/// 字段名和类型两行, 后面可能还有说明行, 见 [config::is_head_row_mark]
+pub const HEAD_ROWS: usize = 2;
 
 // ╔══════╦══════╗
 // ║      ║ head ║
@@ -26,8 +30,12 @@ pub type Cell = Rc<String>;
 pub struct RawTable {
   full_name: String,
   storage: Array2<Cell>,
+  /// 第一格是 `T` 的表, 文件里的行列和 `storage` 是反的
+  transpose: bool,
   main_col: usize,
   data_row: usize,
+  /// 分块读的表, data 区第一行前面在文件里还跳过了多少行
+  row_offset: usize,
 }
 
 impl RawTable {
@@ -35,13 +43,82 @@ impl RawTable {
     let mut rdr = ReaderBuilder::new()
       .has_headers(false)
       .from_path(path.as_ref())?;
+    let records = rdr.records().try_collect::<Vec<_>>()?;
+    Self::from_records(full_name, records)
+  }
+
+  /// 只读前面的 head, 用来定义表的类型
+  pub fn from_csv_head(path: impl AsRef<Path>, full_name: &str) -> Result<Self> {
+    let mut rdr = ReaderBuilder::new()
+      .has_headers(false)
+      .from_path(path.as_ref())?;
+    let records = rdr
+      .records()
+      .enumerate()
+      .map_while(|(i, r)| match r {
+        Ok(r) if i >= HEAD_ROWS && !config::is_head_row_mark(r.get(0).unwrap_or("")) => None,
+        r => Some(r),
+      })
+      .try_collect::<Vec<_>>()?;
+    Self::from_records(full_name, records)
+  }
+
+  /// 和同样内容的 csv 文件完全一样, 第一列是 meta 列
+  pub fn from_grid<S: AsRef<str>>(full_name: &str, grid: &[Vec<S>]) -> Result<Self> {
+    if grid.first().is_none_or(|row| row.is_empty()) {
+      return Err(error::Error::表格为空.into());
+    }
+    if grid.iter().any(|row| row.len() != grid[0].len()) {
+      return Err(error::Error::表格行长度不一致.into());
+    }
+    let records = grid
+      .iter()
+      .map(|row| StringRecord::from(row.iter().map(AsRef::as_ref).collect::<Vec<_>>()));
+    Self::from_records(full_name, records)
+  }
+
+  /// 字段名, 类型, 每行数据分开给, 不用写 meta 列
+  pub fn from_rows<S: AsRef<str>>(
+    full_name: &str,
+    fields: &[S],
+    types: &[S],
+    rows: &[Vec<S>],
+  ) -> Result<Self> {
+    let grid = [fields, types]
+      .into_iter()
+      .chain(rows.iter().map(Vec::as_slice))
+      .map(|row| {
+        std::iter::once("")
+          .chain(row.iter().map(AsRef::as_ref))
+          .collect::<Vec<_>>()
+      })
+      .collect::<Vec<_>>();
+    Self::from_grid(full_name, &grid)
+  }
+
+  pub fn from_records(
+    full_name: &str,
+    records: impl IntoIterator<Item = StringRecord>,
+  ) -> Result<Self> {
+    if !config::is_full_name(full_name) {
+      return Err(error::Error::表名错误(full_name.to_string()).into());
+    }
     let mut column = 0;
     let mut row = 0;
-    let mut cells = Vec::new();
-    for record in rdr.records() {
-      let record = record?;
+    let mut cells: Vec<Cell> = Vec::new();
+    // 同一张表里内容一样的格子共用一份
+    let mut interned: HashSet<Cell> = HashSet::new();
+    for record in records {
       for cell in record.iter() {
-        cells.push(Rc::new(cell.to_string()));
+        let cell = match interned.get(cell) {
+          Some(cell) => cell.clone(),
+          None => {
+            let cell: Cell = Arc::from(cell);
+            interned.insert(cell.clone());
+            cell
+          }
+        };
+        cells.push(cell);
       }
       if column == 0 {
         column = record.len();
@@ -54,34 +131,110 @@ impl RawTable {
     } else {
       Array2::from_shape_fn([column, row], |(i, j)| cells[j * column + i].clone())
     };
+    let data_row = (HEAD_ROWS..storage.shape()[0])
+      .find(|&r| !config::is_head_row_mark(&storage[[r, 0]]))
+      .unwrap_or(storage.shape()[0]);
     Ok(Self {
       full_name: full_name.to_string(),
       storage,
+      transpose,
       main_col: 1,
-      data_row: 2,
+      data_row,
+      row_offset: 0,
     })
   }
 
-  pub fn get_data_area(&self) -> ArrayView2<Cell> {
+  pub fn get_data_area(&self) -> ArrayView2<'_, Cell> {
     self.storage.slice(s![self.data_row.., self.main_col..])
   }
-  pub fn get_head_area(&self) -> ArrayView2<Cell> {
+  pub fn get_head_area(&self) -> ArrayView2<'_, Cell> {
     self.storage.slice(s![..self.data_row, self.main_col..])
   }
 
+  /// data 区的格子在文件里的位置
+  pub fn data_span(&self, row: usize, col: usize) -> Span {
+    let (row, col) = (self.data_row + self.row_offset + row, self.main_col + col);
+    if self.transpose {
+      Span { row: col, col: row }
+    } else {
+      Span { row, col }
+    }
+  }
+
+  /// 字段名, 类型, 和后面的说明行一共几行
+  pub fn head_rows(&self) -> usize {
+    self.data_row
+  }
+
+  /// head 区里 meta 列是 `mark` 的那行的格子
+  pub fn get_head_extra(&self, mark: &str, col: usize) -> Option<&Cell> {
+    (HEAD_ROWS..self.data_row)
+      .find(|&r| self.storage[[r, 0]].trim() == mark)
+      .map(|r| &self.storage[[r, self.main_col + col]])
+  }
+
+  pub fn with_row_offset(mut self, row_offset: usize) -> Self {
+    self.row_offset = row_offset;
+    self
+  }
+
+  pub fn is_transpose(&self) -> bool {
+    self.transpose
+  }
+
   pub fn get_full_name(&self) -> String {
     self.full_name.clone()
   }
 
   pub fn build(&self, database: &mut Database) -> Result<usize> {
+    let head = self.build_head(database)?;
+    let data = self.check_data(&head, database)?;
+    database.add_data(data)
+  }
+
+  /// 只定义表的类型. 表之间可能互相引用, 所有表的类型都定义好之后才能检查数据
+  pub fn build_head(&self, database: &mut Database) -> Result<(Parser, usize)> {
     let mut parser = Parser::new();
     let typ = parser.parse_head(self, database)?;
-    let value = parser.parse_data(self, database)?;
-    Ok(database.add_data(Data {
-      full_name: self.full_name.clone(),
-      typ,
-      value,
-    }))
+    Ok((parser, typ))
+  }
+
+  /// 解析并检查数据, 不改 `database`, 可以多个表同时做
+  pub fn check_data(&self, (parser, typ): &(Parser, usize), database: &Database) -> Result<Data> {
+    let raw_data = parser.parse_data(self, database)?;
+    let value = value::check(database, *typ, &raw_data)
+      .map_err(|e| e.context(format!("{} 的数据错误", self.full_name)))?;
+    UniqueChecker::new(database, &self.full_name, *typ).check_table(&value)?;
+    Ok(Data::new(&self.full_name, *typ, Content::Value(value)))
+  }
+}
+
+/// 别名文件里的一行, 格式: `名字, 类型`. 第一格以 `#` 开头的行是注释
+pub struct RawAlias {
+  pub full_name: String,
+  pub raw_type: String,
+}
+
+impl RawAlias {
+  pub fn from_csv(path: impl AsRef<Path>, module: &str) -> Result<Vec<Self>> {
+    let mut rdr = ReaderBuilder::new()
+      .has_headers(false)
+      .flexible(true)
+      .from_path(path.as_ref())?;
+    let mut res = Vec::new();
+    for record in rdr.records() {
+      let record = record?;
+      let name = record.get(0).unwrap_or("").trim();
+      if name.is_empty() || name.starts_with('#') {
+        continue;
+      }
+      let raw_type = record.get(1).unwrap_or("").trim();
+      res.push(Self {
+        full_name: config::path_join(&[module, name]),
+        raw_type: raw_type.to_string(),
+      });
+    }
+    Ok(res)
   }
 }
 
@@ -96,6 +249,12 @@ pub mod error {
     FileStemError,
     #[error("文件名含有非Unicode字符")]
     OsStrError,
+    #[error("表格为空")]
+    表格为空,
+    #[error("表格行长度不一致")]
+    表格行长度不一致,
+    #[error("表名错误: {0}, 要以 `.` 开头, 每一段都不能为空")]
+    表名错误(String),
   }
 }
 
@@ -103,11 +262,12 @@ pub mod error {
 mod test {
   use crate::basic::{database::Database, raw_table::RawTable};
   use anyhow::Result;
+  use std::sync::Arc;
 
   #[test]
   pub fn test_csv_load() -> Result<()> {
-    let mut raw_table = RawTable::from_csv("./test/a.csv", ".测试表")?;
-    let mut raw_table_t = RawTable::from_csv("./test/a_t.csv", ".测试表")?;
+    let raw_table = RawTable::from_csv("./test/a.csv", ".测试表")?;
+    let raw_table_t = RawTable::from_csv("./test/a_t.csv", ".测试表")?;
     assert_eq!(raw_table.get_head_area(), raw_table_t.get_head_area());
     assert_eq!(raw_table.get_data_area(), raw_table_t.get_data_area());
     dbg!(raw_table.get_head_area());
@@ -116,11 +276,126 @@ mod test {
   }
   #[test]
   pub fn test_raw_table_build() -> Result<()> {
-    let mut raw_table = RawTable::from_csv("./test/a.csv", ".测试表")?;
+    let raw_table = RawTable::from_csv("./test/a.csv", ".测试表")?;
     let mut database = Database::new();
     let table = raw_table.build(&mut database)?;
     dbg!(&table);
     dbg!(&database);
     Ok(())
   }
+  #[test]
+  pub fn test_from_rows() -> Result<()> {
+    let raw_table = RawTable::from_csv("./test/a.csv", ".测试表")?;
+    let raw_table_m = RawTable::from_rows(
+      ".测试表",
+      &[
+        "id",
+        "name",
+        "age",
+        "偏向",
+        "男女",
+        "技能",
+        "",
+        "",
+        "攻击力",
+      ],
+      &["i", "s", "i", "f", "b", "l<i>", "", "", "i"],
+      &[
+        vec!["1", "a", "20", "0.5", "true", "1001", "1002", "1003", "10"],
+        vec!["2", "a", "21", "0.5", "false", " ", "", "", "11"],
+        vec!["3", "a", "22", "0.5", "true", "1201", "", "", "12"],
+      ],
+    )?;
+    assert_eq!(raw_table.get_head_area(), raw_table_m.get_head_area());
+    assert_eq!(raw_table.get_data_area(), raw_table_m.get_data_area());
+    assert!(RawTable::from_grid(".x", &[vec!["", "id"], vec![""]]).is_err());
+    for name in ["物品", "", ".", ".a..b", ".a."] {
+      let err = RawTable::from_rows(name, &["id"], &["i"], &[vec!["1"]])
+        .err()
+        .unwrap();
+      assert!(err.to_string().starts_with("表名错误"), "{err}");
+    }
+    Ok(())
+  }
+  #[test]
+  pub fn test_cell_interned() -> Result<()> {
+    let raw_table = RawTable::from_rows(
+      ".x",
+      &["id", "name"],
+      &["i", "s"],
+      &[vec!["1", "a"], vec!["2", "a"]],
+    )?;
+    let data = raw_table.get_data_area();
+    assert!(Arc::ptr_eq(&data[[0, 1]], &data[[1, 1]]));
+    assert!(!Arc::ptr_eq(&data[[0, 0]], &data[[1, 0]]));
+    Ok(())
+  }
+  #[test]
+  pub fn test_add_tables() -> Result<()> {
+    let mut database = Database::new();
+    let ids = database.add_tables(vec![
+      RawTable::from_rows(".a", &["id", "b"], &["i", "b.b_item?"], &[vec!["1", ""]])?,
+      RawTable::from_rows(".b", &["id"], &["s"], &[vec!["x"], vec!["y"]])?,
+    ])?;
+    assert_eq!(ids.len(), 2);
+    let table = database.table(".b").unwrap();
+    assert_eq!(table.len()?, 2);
+    assert_eq!(table.get("y")?.unwrap().get_str("id")?, "y");
+
+    let err = database
+      .add_table(RawTable::from_rows(".b", &["id"], &["s"], &[vec!["z"]])?)
+      .unwrap_err();
+    assert_eq!(err.to_string(), "表重复: .b");
+    assert!(database.table("b").is_none());
+    let err = Database::new()
+      .add_tables(vec![
+        RawTable::from_rows(".c", &["id"], &["i"], &[vec!["1"]])?,
+        RawTable::from_rows(".c", &["id"], &["i"], &[vec!["2"]])?,
+      ])
+      .unwrap_err();
+    assert_eq!(err.to_string(), "表重复: .c");
+    assert_eq!(database.table(".b").unwrap().len()?, 2);
+    Ok(())
+  }
+  #[test]
+  pub fn test_add_table_after_error() -> Result<()> {
+    let mut database = Database::new();
+    // 类型写错和数据写错都不能留下 `.c.c_item`, 改好之后还能加进去
+    let err = database
+      .add_table(RawTable::from_rows(
+        ".m.c",
+        &["id", "v"],
+        &["i", "x"],
+        &[vec!["1", "2"]],
+      )?)
+      .unwrap_err();
+    assert!(err.to_string().contains(".m.c 的 v 列类型错误"), "{err}");
+    let err = database
+      .add_table(RawTable::from_rows(
+        ".m.c",
+        &["id", "v"],
+        &["i", "i"],
+        &[vec!["1", "a"]],
+      )?)
+      .unwrap_err();
+    assert!(!err.to_string().contains("类型重名"), "{err}");
+    assert!(database.table(".m.c").is_none());
+    assert!(database.get_type_id_by_full_name(".m.c.c_item").is_none());
+    database.add_table(RawTable::from_rows(
+      ".m.c",
+      &["id", "v"],
+      &["i", "i"],
+      &[vec!["1", "2"]],
+    )?)?;
+    assert_eq!(
+      database
+        .table(".m.c")
+        .unwrap()
+        .get(1)?
+        .unwrap()
+        .get_int("v")?,
+      2
+    );
+    Ok(())
+  }
 }
@@ -0,0 +1,295 @@
+//! 标注了 `ref=表名` 的列, 值是另一张表的主键. 生成代码时加载完再把主键换成指向的行
+//!
+//! 表名和类型名一样, 先在这张表所在的模块里找, 找不到再依次去上层模块找. `ref_as=名字` 指定指向的行的字段名
+
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::basic::{
+  config,
+  database::{Database, Type},
+  query::{Key, Table},
+  value::{Value, ValueKind},
+};
+use error::Error::*;
+
+pub const REF_ANNOTATION: &str = "ref";
+pub const REF_AS_ANNOTATION: &str = "ref_as";
+
+/// 列里存的是一个主键, 可空的一个主键, 还是主键的列表
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefShape {
+  One,
+  Optional,
+  List,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+  pub field: Arc<str>,
+  /// 指向的行的字段名, 默认是 `列名_ref`
+  pub name: String,
+  /// 指向的表的全名
+  pub target: String,
+  pub shape: RefShape,
+}
+
+impl Database {
+  /// 表的行里的引用, 按列的顺序. 表不存在或者列的类型和主键对不上时报错
+  pub fn references(&self, table: Table) -> Result<Vec<Reference>> {
+    let Some(Type::Struct { fields, meta, .. }) = self.get_type(table.item_type()) else {
+      return Ok(Vec::new());
+    };
+    let mut res = Vec::new();
+    for (field, &tid) in fields {
+      let Some(annotations) = meta.get(field).map(|m| &m.annotations) else {
+        continue;
+      };
+      let Some(target) = annotations.get(REF_ANNOTATION) else {
+        continue;
+      };
+      let target = self.ref_target(table.full_name(), field, target)?;
+      let target = self.table(&target).ok_or_else(|| {
+        引用的表不存在(table.full_name().to_string(), field.to_string(), target)
+      })?;
+      let tid = self.resolve_alias(tid);
+      let (shape, key) = match *self.get_type(tid).unwrap() {
+        Type::Optional(inner) => (RefShape::Optional, inner),
+        Type::List(item) => (RefShape::List, item),
+        _ => (RefShape::One, tid),
+      };
+      if self.resolve_alias(key) != self.resolve_alias(target.key_type()) {
+        return Err(
+          引用的类型不匹配(
+            table.full_name().to_string(),
+            field.to_string(),
+            self.type_full_name(tid),
+            target.full_name().to_string(),
+            self.type_full_name(target.key_type()),
+          )
+          .into(),
+        );
+      }
+      res.push(Reference {
+        field: field.clone(),
+        name: match annotations.get(REF_AS_ANNOTATION).and_then(|v| v.as_str()) {
+          Some(name) => name.to_string(),
+          None => format!("{field}_ref"),
+        },
+        target: target.full_name().to_string(),
+        shape,
+      });
+    }
+    Ok(res)
+  }
+
+  /// 引用列里的主键都要在指向的表里. 导出时检查, 这时所有的表都加载完了. 分块读的表在写的时候逐行检查
+  pub fn check_references(&self) -> Result<()> {
+    for table in self.tables() {
+      if let Some(value) = table.data().value() {
+        self.check_value_references(table.full_name(), value)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// `value` 是 `table_full_name` 这张表里的值
+  pub(crate) fn check_value_references(&self, table_full_name: &str, value: &Value) -> Result<()> {
+    match &value.kind {
+      ValueKind::Ref { table, key } => {
+        let target = self.table(table).ok_or_else(|| {
+          引用的表不存在(
+            table_full_name.to_string(),
+            String::new(),
+            table.to_string(),
+          )
+        })?;
+        if let Some(k) = Key::from_value(key)
+          && !target.data().key_index().contains_key(&k)
+        {
+          let position = key.span.map(|s| format!(", 在{s}")).unwrap_or_default();
+          return Err(
+            引用的主键不存在(
+              table_full_name.to_string(),
+              key.to_json().to_string(),
+              table.to_string(),
+              position,
+            )
+            .into(),
+          );
+        }
+      }
+      ValueKind::List(items) | ValueKind::Tuple(items) => {
+        for item in items {
+          self.check_value_references(table_full_name, item)?;
+        }
+      }
+      ValueKind::Map(entries) => {
+        for (k, v) in entries {
+          self.check_value_references(table_full_name, k)?;
+          self.check_value_references(table_full_name, v)?;
+        }
+      }
+      ValueKind::Struct(fields) => {
+        for v in fields.values() {
+          self.check_value_references(table_full_name, v)?;
+        }
+      }
+      _ => {}
+    }
+    Ok(())
+  }
+
+  /// `ref` 标注指向的表的全名. 按表的行类型找, 所以一起加的表还没检查完数据时也能找到
+  pub(crate) fn ref_target(
+    &self,
+    table_full_name: &str,
+    field: &str,
+    annotation: &serde_json::Value,
+  ) -> Result<String> {
+    let name = annotation.as_str().unwrap_or_default();
+    let exists = |full_name: &str| {
+      self
+        .get_type_id_by_full_name(&config::table_item_type_full_name(full_name))
+        .is_some_and(|id| {
+          matches!(
+            self.get_type(id),
+            Some(Type::Struct { .. } | Type::Placeholder(_))
+          )
+        })
+    };
+    if name.starts_with(config::PATH_SPLITOR) {
+      if exists(name) {
+        return Ok(name.to_string());
+      }
+    } else {
+      let mut module = config::path_parent(table_full_name);
+      loop {
+        let full_name = config::path_join(&[module, name]);
+        if exists(&full_name) {
+          return Ok(full_name);
+        }
+        if module.is_empty() {
+          break;
+        }
+        module = config::path_parent(module);
+      }
+    }
+    Err(
+      引用的表不存在(
+        table_full_name.to_string(),
+        field.to_string(),
+        name.to_string(),
+      )
+      .into(),
+    )
+  }
+}
+
+pub mod error {
+  use thiserror::Error;
+
+  #[derive(Debug, Error)]
+  pub enum Error {
+    #[error("{0} 的 {1} 列引用的表 {2} 不存在")]
+    引用的表不存在(String, String, String),
+    #[error("{0} 的 {1} 列是 {2}, 和 {3} 的主键 {4} 对不上")]
+    引用的类型不匹配(String, String, String, String, String),
+    #[error("{0} 里的 {1} 不在 {2} 里{3}")]
+    引用的主键不存在(String, String, String, String),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use anyhow::Result;
+
+  use crate::basic::{
+    database::Database,
+    raw_table::RawTable,
+    reference::{RefShape, Reference},
+  };
+
+  #[test]
+  fn test_references() -> Result<()> {
+    let mut db = Database::new();
+    db.add_tables(vec![
+      RawTable::from_grid(".物品", &[vec!["", "id"], vec!["", "i"], vec!["", "1"]])?,
+      RawTable::from_grid(
+        ".商店.货架",
+        &[
+          vec!["", "id", "物品", "赠品", "名字"],
+          vec!["", "i", "i", "i?", "s"],
+          vec!["标注", "", "ref=物品, ref_as=item", "ref=.物品", "ref=物品"],
+          vec!["", "1", "1", "", "a"],
+        ],
+      )?,
+    ])?;
+    let err = db.references(db.table(".商店.货架").unwrap()).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      ".商店.货架 的 名字 列是 .string, 和 .物品 的主键 .int 对不上"
+    );
+
+    let mut db = Database::new();
+    db.add_tables(vec![
+      RawTable::from_grid(".物品", &[vec!["", "id"], vec!["", "i"], vec!["", "1"]])?,
+      RawTable::from_grid(
+        ".商店.货架",
+        &[
+          vec!["", "id", "物品", "赠品"],
+          vec!["", "i", "i", "i?"],
+          vec!["标注", "", "ref=物品, ref_as=item", "ref=.物品"],
+          vec!["", "1", "1", ""],
+        ],
+      )?,
+    ])?;
+    let refs = db.references(db.table(".商店.货架").unwrap())?;
+    assert_eq!(
+      refs,
+      [
+        Reference {
+          field: "物品".into(),
+          name: "item".to_string(),
+          target: ".物品".to_string(),
+          shape: RefShape::One,
+        },
+        Reference {
+          field: "赠品".into(),
+          name: "赠品_ref".to_string(),
+          target: ".物品".to_string(),
+          shape: RefShape::Optional,
+        },
+      ]
+    );
+    db.check_references()?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_dangling_reference() -> Result<()> {
+    let mut db = Database::new();
+    db.add_tables(vec![
+      RawTable::from_grid(".物品", &[vec!["", "id"], vec!["", "i"], vec!["", "1"]])?,
+      RawTable::from_grid(
+        ".商店.货架",
+        &[
+          vec!["", "id", "物品"],
+          vec!["", "i", "i?"],
+          vec!["标注", "", "ref=物品"],
+          vec!["", "1", "1"],
+          vec!["", "2", "2"],
+        ],
+      )?,
+    ])?;
+    let err = db.check_references().unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      ".商店.货架 里的 2 不在 .物品 里, 在第 5 行第 3 列"
+    );
+    let out = std::env::temp_dir().join("reference_test_dangling_reference");
+    assert!(db.generate_data(&out).is_err());
+    Ok(())
+  }
+}
@@ -0,0 +1,350 @@
+//! 和目标语言无关的结构描述
+//!
+//! 可以导出成 json 给别的工具用, 也可以直接当 handlebars 模板的上下文, 不用改 rust 就能生成新语言的代码
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::basic::{
+  code_generator, config,
+  database::{Content, Database, Type},
+};
+
+#[derive(Debug, Serialize)]
+pub struct Schema {
+  /// 不含根模块, 父模块在子模块前面
+  pub modules: Vec<ModuleSchema>,
+  pub tables: Vec<TableSchema>,
+  /// 每个表的行类型
+  pub structs: Vec<StructSchema>,
+  pub aliases: Vec<AliasSchema>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModuleSchema {
+  pub full_name: String,
+  pub name: String,
+  pub parent: String,
+  /// 这个模块本身就是一张表时, 表的全名
+  pub table: Option<String>,
+  pub children: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableSchema {
+  pub full_name: String,
+  pub name: String,
+  pub module: String,
+  /// 第一列
+  pub key_field: String,
+  pub key: TypeRef,
+  /// 行类型的全名, 在 [Schema::structs] 里
+  pub item: String,
+  /// 分块读写, 不在内存里
+  pub streamed: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StructSchema {
+  pub full_name: String,
+  pub name: String,
+  pub module: String,
+  /// 按列的顺序
+  pub fields: Vec<FieldSchema>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldSchema {
+  pub name: String,
+  #[serde(rename = "type")]
+  pub typ: TypeRef,
+  /// 类型是 `?`, 可以不填
+  pub optional: bool,
+  pub default: Option<String>,
+  pub description: Option<String>,
+  pub annotations: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AliasSchema {
+  pub full_name: String,
+  pub name: String,
+  pub module: String,
+  pub target: TypeRef,
+}
+
+/// `name` 是类型的全名, 例: `.list<.int>`
+#[derive(Debug, Serialize)]
+pub struct TypeRef {
+  pub name: String,
+  #[serde(flatten)]
+  pub kind: TypeKind,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TypeKind {
+  Dynamic,
+  Int,
+  Float,
+  String,
+  Bool,
+  DateTime,
+  Date,
+  Duration,
+  Fixed {
+    int_bits: u8,
+    frac_bits: u8,
+  },
+  Optional {
+    inner: Box<TypeRef>,
+  },
+  List {
+    item: Box<TypeRef>,
+  },
+  Dict {
+    key: Box<TypeRef>,
+    value: Box<TypeRef>,
+  },
+  Tuple {
+    items: Vec<TupleItem>,
+  },
+  /// 具体字段在 [Schema::structs] 里按 `name` 找
+  Struct,
+  /// 别名本身在 [Schema::aliases] 里, 这里直接给出实际类型
+  Alias {
+    target: Box<TypeRef>,
+  },
+}
+
+#[derive(Debug, Serialize)]
+pub struct TupleItem {
+  pub name: Option<String>,
+  #[serde(rename = "type")]
+  pub typ: TypeRef,
+}
+
+impl Database {
+  pub fn schema(&self) -> Schema {
+    let modules = self
+      .modules
+      .root()
+      .descendants()
+      .skip(1)
+      .map(|m| {
+        let full_name = self.module_full_name(m.id());
+        ModuleSchema {
+          name: m.value().name.clone(),
+          parent: config::path_parent(&full_name).to_string(),
+          table: m.value().data.map(|did| self.data[did].full_name.clone()),
+          children: m.value().children_name_to_id.keys().cloned().collect(),
+          full_name,
+        }
+      })
+      .collect();
+
+    let tables = self
+      .tables()
+      .map(|table| {
+        let full_name = table.full_name();
+        TableSchema {
+          full_name: full_name.to_string(),
+          name: config::path_name(full_name).to_string(),
+          module: config::path_parent(full_name).to_string(),
+          key_field: table
+            .fields()
+            .first()
+            .map(|f| f.0.to_string())
+            .unwrap_or_default(),
+          key: self.type_ref(table.key_type()),
+          item: self.type_full_name(table.item_type()),
+          streamed: matches!(table.data().content, Content::Stream(_)),
+        }
+      })
+      .collect();
+
+    let mut structs = Vec::new();
+    let mut aliases = Vec::new();
+    for ty in self.types.iter() {
+      match ty {
+        Type::Struct {
+          full_name,
+          fields,
+          meta,
+        } => structs.push(StructSchema {
+          full_name: full_name.clone(),
+          name: config::path_name(full_name).to_string(),
+          module: config::path_parent(full_name).to_string(),
+          fields: fields
+            .iter()
+            .map(|(name, &tid)| {
+              let meta = meta.get(name).cloned().unwrap_or_default();
+              FieldSchema {
+                name: name.to_string(),
+                typ: self.type_ref(tid),
+                optional: self
+                  .get_type(self.resolve_alias(tid))
+                  .is_some_and(Type::is_optional),
+                default: meta.default,
+                description: meta.description,
+                annotations: meta.annotations,
+              }
+            })
+            .collect(),
+        }),
+        Type::Alias { full_name, target } => aliases.push(AliasSchema {
+          full_name: full_name.clone(),
+          name: config::path_name(full_name).to_string(),
+          module: config::path_parent(full_name).to_string(),
+          target: self.type_ref(*target),
+        }),
+        _ => {}
+      }
+    }
+
+    Schema {
+      modules,
+      tables,
+      structs,
+      aliases,
+    }
+  }
+
+  fn type_ref(&self, tid: usize) -> TypeRef {
+    let boxed = |tid| Box::new(self.type_ref(tid));
+    let kind = match &self.types[tid] {
+      Type::Unknown | Type::Placeholder(_) => unreachable!("加载完成后不会有未定义的类型"),
+      Type::Dynamic => TypeKind::Dynamic,
+      Type::Int => TypeKind::Int,
+      Type::Float => TypeKind::Float,
+      Type::String => TypeKind::String,
+      Type::Bool => TypeKind::Bool,
+      Type::DateTime => TypeKind::DateTime,
+      Type::Date => TypeKind::Date,
+      Type::Duration => TypeKind::Duration,
+      &Type::Fixed {
+        int_bits,
+        frac_bits,
+      } => TypeKind::Fixed {
+        int_bits,
+        frac_bits,
+      },
+      &Type::Optional(inner) => TypeKind::Optional {
+        inner: boxed(inner),
+      },
+      &Type::List(item) => TypeKind::List { item: boxed(item) },
+      &Type::Dict(key, value) => TypeKind::Dict {
+        key: boxed(key),
+        value: boxed(value),
+      },
+      Type::Tuple { items, names } => TypeKind::Tuple {
+        items: items
+          .iter()
+          .zip(names)
+          .map(|(&tid, name)| TupleItem {
+            name: name.clone(),
+            typ: self.type_ref(tid),
+          })
+          .collect(),
+      },
+      Type::Struct { .. } => TypeKind::Struct,
+      &Type::Alias { target, .. } => TypeKind::Alias {
+        target: boxed(target),
+      },
+    };
+    TypeRef {
+      name: self.type_full_name(tid),
+      kind,
+    }
+  }
+}
+
+impl Schema {
+  pub fn to_json(&self) -> serde_json::Value {
+    serde_json::to_value(self).expect("schema 只有字符串键")
+  }
+
+  /// 用 handlebars 模板渲染, 上下文就是 [Schema::to_json] 的结果, 不转义, 能用生成器共用的 helper
+  pub fn render(&self, template: &str) -> Result<String> {
+    let reg = code_generator::new_registry(&[], None)?;
+    Ok(reg.render_template(template, self)?)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::basic::{database::Database, raw_table::RawTable};
+  use anyhow::Result;
+
+  #[test]
+  fn test_schema() -> Result<()> {
+    let mut db = Database::new();
+    db.add_table(RawTable::from_grid(
+      ".物品",
+      &[
+        vec!["", "id", "name", "类型", "备注"],
+        vec!["", "i", "s", "i", "i?"],
+        vec!["描述", "编号", "名字", "", ""],
+        vec!["标注", "", "index, unique", "index", "ref=.物品"],
+        vec!["默认", "", "", "3", ""],
+        vec!["", "1", "a", "", ""],
+        vec!["", "2", "b", "5", "1"],
+      ],
+    )?)?;
+    let row = db.table(".物品").unwrap().get(1)?.unwrap();
+    assert_eq!(row.get_int("类型")?, 3);
+    assert!(row.get_optional("备注")?.is_none());
+    assert_eq!(
+      db.table(".物品")
+        .unwrap()
+        .get(2)?
+        .unwrap()
+        .get_int("类型")?,
+      5
+    );
+
+    let schema = db.schema().to_json();
+    assert_eq!(schema["tables"][0]["full_name"], ".物品");
+    assert_eq!(schema["tables"][0]["key"]["kind"], "int");
+    assert_eq!(schema["tables"][0]["item"], ".物品.物品_item");
+    let fields = &schema["structs"][0]["fields"];
+    let names = fields
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|f| f["name"].clone())
+      .collect::<Vec<_>>();
+    assert_eq!(names, ["id", "name", "类型", "备注"]);
+    assert_eq!(fields[0]["description"], "编号");
+    assert_eq!(fields[1]["annotations"]["unique"], true);
+    assert_eq!(fields[2]["default"], "3");
+    assert_eq!(fields[3]["annotations"]["ref"], ".物品");
+    assert_eq!(fields[3]["optional"], true);
+    assert_eq!(fields[3]["type"]["inner"]["kind"], "int");
+
+    let out = db
+      .schema()
+      .render("{{#each tables}}{{name}}:{{key.kind}};{{/each}}")?;
+    assert_eq!(out, "物品:int;");
+    let out = db
+      .schema()
+      .render(r#"{{#each structs}}{{pascal_case fields.1.name}}{{/each}}"#)?;
+    assert_eq!(out, "Name");
+    Ok(())
+  }
+
+  #[test]
+  fn test_schema_project() -> Result<()> {
+    let mut db = Database::new();
+    db.load_project("./test/proj/")?;
+    let schema = db.schema();
+    assert!(schema.tables.iter().any(|t| t.full_name == ".命名空间.a"));
+    assert!(
+      schema
+        .modules
+        .iter()
+        .any(|m| m.full_name == ".命名空间" && m.table.is_none())
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,250 @@
+//! 很大的表不整张读进内存
+//!
+//! 加载时读一遍检查数据, 只留下主键. 导出时再一块一块地读, 写出. 内存里最多只有一块的数据.
+//! 不在内存里的表不能用 [crate::basic::query] 和 [crate::basic::de] 读, 会返回错误
+
+use std::{
+  io::Write,
+  path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use csv::ReaderBuilder;
+
+use crate::{
+  HashMap,
+  basic::{
+    config::DictLayout,
+    database::{Data, Database},
+    index::UniqueChecker,
+    parser::Parser,
+    query::Key,
+    raw_table::RawTable,
+    value::{self, Value, ValueKind},
+  },
+};
+use error::Error::*;
+
+/// 每次读这么多行
+pub const CHUNK_ROWS: usize = 256;
+
+#[derive(Debug)]
+pub struct StreamSource {
+  pub path: PathBuf,
+  head_rows: usize,
+  parser: Parser,
+}
+
+impl StreamSource {
+  pub fn new(path: impl AsRef<Path>, head_rows: usize, parser: Parser) -> Self {
+    Self {
+      path: path.as_ref().to_path_buf(),
+      head_rows,
+      parser,
+    }
+  }
+
+  /// 按块读出每一行, 检查过数据, 主键和 `unique` 的列不重复
+  fn for_each_entry(
+    &self,
+    full_name: &str,
+    typ: usize,
+    database: &Database,
+    mut f: impl FnMut(Value, Value) -> Result<()>,
+  ) -> Result<()> {
+    let mut rdr = ReaderBuilder::new()
+      .has_headers(false)
+      .from_path(&self.path)?;
+    let mut records = rdr.records();
+    let head = records
+      .by_ref()
+      .take(self.head_rows)
+      .try_collect::<Vec<_>>()?;
+    let mut unique = UniqueChecker::new(database, full_name, typ);
+    let mut row_offset = 0;
+    loop {
+      let chunk = records.by_ref().take(CHUNK_ROWS).try_collect::<Vec<_>>()?;
+      if chunk.is_empty() {
+        break;
+      }
+      let rows = chunk.len();
+      let raw_table = RawTable::from_records(full_name, head.iter().cloned().chain(chunk))?
+        .with_row_offset(row_offset);
+      let raw_data = self.parser.parse_data(&raw_table, database)?;
+      let value = value::check(database, typ, &raw_data)
+        .map_err(|e| e.context(format!("{full_name} 的数据错误")))?;
+      let ValueKind::Map(entries) = value.kind else {
+        return Err(表的类型不是字典.into());
+      };
+      for (k, v) in entries {
+        unique.check_entry(&k, &v)?;
+        f(k, v)?;
+      }
+      row_offset += rows;
+    }
+    Ok(())
+  }
+
+  /// 加载时读一遍, 数据有错这时就报出来. 返回主键到行号, 和整张表的一样
+  pub fn check(
+    &self,
+    full_name: &str,
+    typ: usize,
+    database: &Database,
+  ) -> Result<HashMap<Key, usize>> {
+    let mut index = HashMap::new();
+    let mut row = 0;
+    self.for_each_entry(full_name, typ, database, |k, _| {
+      if let Some(k) = Key::from_value(&k) {
+        index.insert(k, row);
+      }
+      row += 1;
+      Ok(())
+    })?;
+    Ok(index)
+  }
+
+  /// 和整张表导出的 json 完全一样. 引用列在这时检查, 见 [Database::check_references]
+  pub fn write_json(&self, data: &Data, database: &Database, mut writer: impl Write) -> Result<()> {
+    let layout = database.options.dict_layout;
+    writer.write_all(match layout {
+      DictLayout::Object => b"{",
+      DictLayout::Entries => b"{\"entries\":[",
+    })?;
+    let mut first = true;
+    self.for_each_entry(&data.full_name, data.typ, database, |k, v| {
+      database.check_value_references(&data.full_name, &v)?;
+      if !first {
+        writer.write_all(b",")?;
+      }
+      first = false;
+      match layout {
+        DictLayout::Object => {
+          serde_json::to_writer(&mut writer, &k.to_json().to_string())?;
+          writer.write_all(b":")?;
+          serde_json::to_writer(&mut writer, &v.to_json_as(layout))?;
+        }
+        DictLayout::Entries => serde_json::to_writer(
+          &mut writer,
+          &value::entry_json(k.to_json_as(layout), v.to_json_as(layout)),
+        )?,
+      }
+      Ok(())
+    })?;
+    writer.write_all(match layout {
+      DictLayout::Object => b"}",
+      DictLayout::Entries => b"]}",
+    })?;
+    Ok(())
+  }
+}
+
+pub mod error {
+  use thiserror::Error;
+
+  #[derive(Debug, Error)]
+  pub enum Error {
+    #[error("表的类型不是字典")]
+    表的类型不是字典,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs;
+
+  use crate::basic::{
+    config::{DictLayout, Options},
+    database::Database,
+  };
+  use anyhow::Result;
+
+  fn export(proj: &str, stream_threshold: Option<u64>, dict_layout: DictLayout) -> Result<Vec<u8>> {
+    let mut db = Database::with_options(Options {
+      stream_threshold,
+      dict_layout,
+      ..Options::default()
+    });
+    db.load_project(proj)?;
+    let data = db.data.iter().find(|d| d.full_name == ".大表").unwrap();
+    assert_eq!(data.value().is_none(), stream_threshold.is_some());
+    let mut out = Vec::new();
+    data.write_json(&db, &mut out)?;
+    Ok(out)
+  }
+
+  #[test]
+  fn test_stream_same_as_whole() -> Result<()> {
+    let proj = std::env::temp_dir().join("stream_test_same_as_whole");
+    fs::create_dir_all(&proj)?;
+    let mut csv = ",id,name,奖励,\n,i,s,\"t<i, i>\",\n".to_string();
+    for i in 0..600 {
+      csv += &format!(",{i},名字{i},{},{}\n", i * 2, i % 7);
+    }
+    fs::write(proj.join("大表.csv"), csv)?;
+    let proj = proj.to_str().unwrap();
+    assert_eq!(
+      export(proj, Some(0), DictLayout::Object)?,
+      export(proj, None, DictLayout::Object)?
+    );
+    let entries = export(proj, Some(0), DictLayout::Entries)?;
+    assert_eq!(entries, export(proj, None, DictLayout::Entries)?);
+    assert!(entries.starts_with(br#"{"entries":[{"key":0,"value":{"#));
+    Ok(())
+  }
+
+  #[test]
+  fn test_stream_error_position() -> Result<()> {
+    let proj = std::env::temp_dir().join("stream_test_error_position");
+    fs::create_dir_all(&proj)?;
+    let mut csv = ",id,v\n,i,i\n".to_string();
+    for i in 0..400 {
+      csv += &format!(
+        ",{i},{}\n",
+        if i == 300 {
+          "x".to_string()
+        } else {
+          i.to_string()
+        }
+      );
+    }
+    fs::write(proj.join("大表.csv"), csv)?;
+    let err = export(proj.to_str().unwrap(), Some(0), DictLayout::Object).unwrap_err();
+    assert!(format!("{err:#}").contains("第 303 行第 3 列"), "{err:#}");
+    Ok(())
+  }
+
+  #[test]
+  fn test_stream_checked_at_load() -> Result<()> {
+    let proj = std::env::temp_dir().join("stream_test_checked_at_load");
+    fs::create_dir_all(&proj)?;
+    let mut csv = ",id,v\n,i,i\n".to_string();
+    for i in 0..400 {
+      csv += &format!(",{},{i}\n", if i == 399 { 5 } else { i });
+    }
+    fs::write(proj.join("大表.csv"), csv)?;
+    // 两种加载方式对重复的主键报一样的错, 都在加载时
+    let load = |stream_threshold| {
+      Database::with_options(Options {
+        stream_threshold,
+        ..Options::default()
+      })
+      .load_project(&proj)
+      .map_err(|e| format!("{e:#}"))
+    };
+    let err = load(Some(0)).unwrap_err();
+    assert_eq!(err, ".大表 的主键 5 重复, 在第 402 行第 2 列");
+    assert_eq!(load(None).unwrap_err(), err);
+
+    fs::write(proj.join("大表.csv"), ",id,v\n,i,i\n,1,10\n,2,20\n")?;
+    let mut db = Database::with_options(Options {
+      stream_threshold: Some(0),
+      ..Options::default()
+    });
+    db.load_project(&proj)?;
+    let table = db.table(".大表").unwrap();
+    assert!(table.get(1).is_err());
+    assert_eq!(table.data().key_index().len(), 2);
+    Ok(())
+  }
+}
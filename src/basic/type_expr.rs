@@ -0,0 +1,345 @@
+//! 类型声明的语法
+//!
+//! ```text
+//! type  := (path args? | '(' items ')') '?'*
+//! path  := '.'? word ('.' word)*
+//! args  := '<' items '>'
+//! items := item (',' item)*
+//! item  := type word?
+//! ```
+//!
+//! 例: `i`, `l<i>`, `d<i, l<s>>`, `t<i itemId, i count>`, `(i, f)`, `i?`, `.a.a_item`, `fx16.16`
+
+use anyhow::Result;
+
+use error::Error::*;
+
+/// 字符位置, 不是字节位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+  pub start: usize,
+  pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeExprKind {
+  /// 内置类型或者自定义类型, 例: `i`, `l<i>`, `fx16.16`, `.a.a_item`
+  Named { path: String, args: Vec<TypeArg> },
+  /// `(i, f)`
+  Tuple(Vec<TypeArg>),
+  /// `i?`
+  Optional(Box<TypeExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeExpr {
+  pub kind: TypeExprKind,
+  pub span: Span,
+}
+
+/// 类型参数, 元组的元素可以带名字
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeArg {
+  pub typ: TypeExpr,
+  pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Word(String),
+  Dot,
+  Lt,
+  Gt,
+  Comma,
+  LParen,
+  RParen,
+  Question,
+  End,
+}
+
+impl Token {
+  fn describe(&self) -> String {
+    match self {
+      Token::Word(w) => format!("`{w}`"),
+      Token::Dot => "`.`".to_string(),
+      Token::Lt => "`<`".to_string(),
+      Token::Gt => "`>`".to_string(),
+      Token::Comma => "`,`".to_string(),
+      Token::LParen => "`(`".to_string(),
+      Token::RParen => "`)`".to_string(),
+      Token::Question => "`?`".to_string(),
+      Token::End => "结尾".to_string(),
+    }
+  }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, Span)>> {
+  let chars = text.chars().collect::<Vec<_>>();
+  let mut tokens = Vec::new();
+  let mut i = 0;
+  while i < chars.len() {
+    let c = chars[i];
+    let start = i;
+    let token = match c {
+      _ if c.is_whitespace() => {
+        i += 1;
+        continue;
+      }
+      '.' => Token::Dot,
+      '<' => Token::Lt,
+      '>' => Token::Gt,
+      ',' => Token::Comma,
+      '(' => Token::LParen,
+      ')' => Token::RParen,
+      '?' => Token::Question,
+      _ if is_word_char(c) => {
+        while i + 1 < chars.len() && is_word_char(chars[i + 1]) {
+          i += 1;
+        }
+        Token::Word(chars[start..=i].iter().collect())
+      }
+      _ => return Err(error(text, start, format!("无法识别的字符 `{c}`")).into()),
+    };
+    i += 1;
+    tokens.push((token, Span { start, end: i }));
+  }
+  tokens.push((
+    Token::End,
+    Span {
+      start: chars.len(),
+      end: chars.len(),
+    },
+  ));
+  Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '_'
+}
+
+struct Parser<'a> {
+  text: &'a str,
+  tokens: Vec<(Token, Span)>,
+  pos: usize,
+}
+
+impl Parser<'_> {
+  fn peek(&self) -> &Token {
+    &self.tokens[self.pos].0
+  }
+  fn span(&self) -> Span {
+    self.tokens[self.pos].1
+  }
+  fn last_end(&self) -> usize {
+    if self.pos == 0 {
+      0
+    } else {
+      self.tokens[self.pos - 1].1.end
+    }
+  }
+  fn bump(&mut self) -> (Token, Span) {
+    let t = self.tokens[self.pos].clone();
+    if t.0 != Token::End {
+      self.pos += 1;
+    }
+    t
+  }
+  fn unexpected(&self, expected: &str) -> anyhow::Error {
+    error(
+      self.text,
+      self.span().start,
+      format!("需要{expected}, 却是{}", self.peek().describe()),
+    )
+    .into()
+  }
+
+  fn parse_type(&mut self) -> Result<TypeExpr> {
+    let start = self.span().start;
+    let kind = match self.peek() {
+      Token::LParen => {
+        self.bump();
+        TypeExprKind::Tuple(self.parse_items(Token::RParen, "`)`")?)
+      }
+      Token::Dot | Token::Word(_) => {
+        let path = self.parse_path()?;
+        let args = if *self.peek() == Token::Lt {
+          self.bump();
+          self.parse_items(Token::Gt, "`>`")?
+        } else {
+          Vec::new()
+        };
+        TypeExprKind::Named { path, args }
+      }
+      _ => return Err(self.unexpected("类型")),
+    };
+    let mut expr = TypeExpr {
+      kind,
+      span: Span {
+        start,
+        end: self.last_end(),
+      },
+    };
+    while *self.peek() == Token::Question {
+      self.bump();
+      expr = TypeExpr {
+        kind: TypeExprKind::Optional(Box::new(expr)),
+        span: Span {
+          start,
+          end: self.last_end(),
+        },
+      };
+    }
+    Ok(expr)
+  }
+
+  fn parse_path(&mut self) -> Result<String> {
+    let mut path = String::new();
+    if *self.peek() == Token::Dot {
+      self.bump();
+      path.push('.');
+    }
+    loop {
+      let Token::Word(w) = self.peek() else {
+        return Err(self.unexpected("名字"));
+      };
+      path += w;
+      self.bump();
+      if *self.peek() != Token::Dot {
+        return Ok(path);
+      }
+      self.bump();
+      path.push('.');
+    }
+  }
+
+  fn parse_items(&mut self, close: Token, close_desc: &str) -> Result<Vec<TypeArg>> {
+    let mut items = Vec::new();
+    loop {
+      let typ = self.parse_type()?;
+      let name = if let Token::Word(w) = self.peek() {
+        let w = w.clone();
+        self.bump();
+        Some(w)
+      } else {
+        None
+      };
+      items.push(TypeArg { typ, name });
+      match self.peek() {
+        Token::Comma => {
+          self.bump();
+        }
+        t if *t == close => {
+          self.bump();
+          return Ok(items);
+        }
+        _ => return Err(self.unexpected(&format!("`,` 或{close_desc}"))),
+      }
+    }
+  }
+}
+
+/// 解析一个类型声明
+pub fn parse(text: &str) -> Result<TypeExpr> {
+  let mut parser = Parser {
+    text,
+    tokens: tokenize(text)?,
+    pos: 0,
+  };
+  let expr = parser.parse_type()?;
+  if *parser.peek() != Token::End {
+    return Err(parser.unexpected("结尾"));
+  }
+  Ok(expr)
+}
+
+/// 构造带位置的错误, 解析之后检查类型时也用这个
+pub fn error(text: &str, pos: usize, message: impl Into<String>) -> error::Error {
+  类型声明错误 {
+    text: text.to_string(),
+    pos,
+    message: message.into(),
+  }
+}
+
+/// 标出出错位置, 中文算两个字宽
+fn show_position(text: &str, pos: &usize) -> String {
+  let width = text
+    .chars()
+    .take(*pos)
+    .map(|c| if c.is_ascii() { 1 } else { 2 })
+    .sum::<usize>();
+  format!("  {text}\n  {}^", " ".repeat(width))
+}
+
+pub mod error {
+  use thiserror::Error;
+
+  use super::show_position;
+
+  #[derive(Debug, Error)]
+  pub enum Error {
+    #[error("类型声明错误 (第 {} 个字符): {message}\n{}", pos + 1, show_position(text, pos))]
+    类型声明错误 {
+      text: String,
+      pos: usize,
+      message: String,
+    },
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::basic::type_expr::*;
+
+  fn named(expr: &TypeExpr) -> (&str, Vec<&TypeExpr>) {
+    match &expr.kind {
+      TypeExprKind::Named { path, args } => (path, args.iter().map(|a| &a.typ).collect()),
+      _ => panic!("{expr:?}"),
+    }
+  }
+
+  #[test]
+  fn test_parse_nested() {
+    let expr = parse("d< i , d<s,l<i>> >").unwrap();
+    let (path, args) = named(&expr);
+    assert_eq!(path, "d");
+    assert_eq!(named(args[0]).0, "i");
+    let (path, args) = named(args[1]);
+    assert_eq!(path, "d");
+    assert_eq!(named(args[1]).0, "l");
+    assert_eq!(args[1].span, Span { start: 11, end: 15 });
+  }
+
+  #[test]
+  fn test_parse_tuple_and_optional() {
+    let expr = parse("(i itemId, l<f>?)?").unwrap();
+    let TypeExprKind::Optional(inner) = &expr.kind else {
+      panic!()
+    };
+    let TypeExprKind::Tuple(items) = &inner.kind else {
+      panic!()
+    };
+    assert_eq!(items[0].name.as_deref(), Some("itemId"));
+    assert!(matches!(items[1].typ.kind, TypeExprKind::Optional(_)));
+  }
+
+  #[test]
+  fn test_parse_path() {
+    assert_eq!(
+      named(&parse(".命名空间.a.a_item").unwrap()).0,
+      ".命名空间.a.a_item"
+    );
+    assert_eq!(named(&parse("fx16.16").unwrap()).0, "fx16.16");
+  }
+
+  #[test]
+  fn test_parse_error_position() {
+    let err = parse("l<i,>").unwrap_err().to_string();
+    assert!(err.contains("第 5 个字符"), "{err}");
+    let err = parse("d<i,i").unwrap_err().to_string();
+    assert!(err.contains("第 6 个字符"), "{err}");
+    let err = parse("l<i> x").unwrap_err().to_string();
+    assert!(err.contains("第 6 个字符"), "{err}");
+    assert!(parse("l<i;>").is_err());
+  }
+}
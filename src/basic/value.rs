@@ -0,0 +1,536 @@
+//! 检查过类型的数据
+//!
+//! 表里的格子只在这里解析一次, 之后导出 json 或者别的格式都用这里的结果
+
+use std::{fmt, sync::Arc};
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeDelta};
+use ego_tree::{NodeRef, Tree};
+use serde_json::{Map, Number};
+use strum::{EnumIs, EnumTryAs};
+
+use crate::{
+  IndexMap,
+  basic::{
+    config::{self, DictLayout},
+    database::{Database, RawData, Type},
+    datetime, fixed, parser, reference,
+  },
+};
+use error::Error::*;
+
+/// 格子在 csv 文件里的位置, 从 0 开始. 转置的表也按文件里的行列算
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+  pub row: usize,
+  pub col: usize,
+}
+
+impl fmt::Display for Span {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "第 {} 行第 {} 列", self.row + 1, self.col + 1)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, EnumIs, EnumTryAs)]
+pub enum ValueKind {
+  /// [Type::Optional] 没填
+  Null,
+  Int(i64),
+  /// 超过 [i64::MAX] 的正整数
+  UInt(u64),
+  Float(f64),
+  String(String),
+  Bool(bool),
+  DateTime(DateTime<FixedOffset>),
+  Date(NaiveDate),
+  Duration(TimeDelta),
+  /// 定点数的原始整数值
+  Fixed(i64),
+  List(Vec<Value>),
+  Tuple(Vec<Value>),
+  /// 按表里的顺序
+  Map(Vec<(Value, Value)>),
+  /// 按列的顺序
+  Struct(IndexMap<Arc<str>, Value>),
+  /// 标注了 `ref` 的列里的主键, `table` 是指向的表的全名. 列表里的每个主键各是一个
+  Ref {
+    table: Arc<str>,
+    key: Box<Value>,
+  },
+}
+
+impl ValueKind {
+  /// [ValueKind::Ref] 给出里面的主键, 其他的不变
+  pub fn deref_key(&self) -> &ValueKind {
+    match self {
+      ValueKind::Ref { key, .. } => &key.kind,
+      kind => kind,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Value {
+  pub kind: ValueKind,
+  /// 值来自哪个格子, 多个格子组成的值取第一个格子. 整张表和空列表没有位置
+  pub span: Option<Span>,
+}
+
+impl Value {
+  pub fn new(kind: ValueKind, span: Option<Span>) -> Self {
+    Self { kind, span }
+  }
+
+  pub fn to_json(&self) -> serde_json::Value {
+    self.to_json_as(DictLayout::Object)
+  }
+
+  pub fn to_json_as(&self, layout: DictLayout) -> serde_json::Value {
+    use serde_json::Value as Json;
+    match &self.kind {
+      ValueKind::Null => Json::Null,
+      ValueKind::Int(v) => Json::from(*v),
+      ValueKind::UInt(v) => Json::from(*v),
+      ValueKind::Float(v) => Number::from_f64(*v).map_or(Json::Null, Json::Number),
+      ValueKind::String(v) => Json::from(v.clone()),
+      ValueKind::Bool(v) => Json::from(*v),
+      ValueKind::DateTime(v) => Json::from(datetime::format_datetime(v)),
+      ValueKind::Date(v) => Json::from(datetime::format_date(v)),
+      ValueKind::Duration(v) => Json::from(datetime::format_duration(*v)),
+      ValueKind::Fixed(v) => Json::from(*v),
+      ValueKind::List(items) | ValueKind::Tuple(items) => Json::from(
+        items
+          .iter()
+          .map(|v| v.to_json_as(layout))
+          .collect::<Vec<_>>(),
+      ),
+      // json 的键只能是字符串, 把键的 json 再转一次字符串
+      ValueKind::Map(entries) if layout == DictLayout::Object => Json::from(
+        entries
+          .iter()
+          .map(|(k, v)| (k.to_json().to_string(), v.to_json_as(layout)))
+          .collect::<Map<_, _>>(),
+      ),
+      ValueKind::Map(entries) => entries_json(
+        entries
+          .iter()
+          .map(|(k, v)| entry_json(k.to_json_as(layout), v.to_json_as(layout)))
+          .collect(),
+      ),
+      ValueKind::Struct(fields) => Json::from(
+        fields
+          .iter()
+          .map(|(k, v)| (k.to_string(), v.to_json_as(layout)))
+          .collect::<Map<_, _>>(),
+      ),
+      ValueKind::Ref { key, .. } => key.to_json_as(layout),
+    }
+  }
+}
+
+/// [DictLayout::Entries] 里的一项
+pub fn entry_json(key: serde_json::Value, value: serde_json::Value) -> serde_json::Value {
+  serde_json::json!({ "key": key, "value": value })
+}
+
+/// [DictLayout::Entries] 写法的字典
+pub fn entries_json(entries: Vec<serde_json::Value>) -> serde_json::Value {
+  serde_json::json!({ "entries": entries })
+}
+
+/// 按类型检查 [RawData] 树, 把格子解析成 [Value]
+pub fn check(database: &Database, typ: usize, tree: &Tree<RawData>) -> Result<Value> {
+  let mut value = check_node(database, typ, tree.root())?.ok_or(值为空)?;
+  value.span = None;
+  Ok(value)
+}
+
+/// 节点下第一个格子的位置
+fn first_span(node: NodeRef<RawData>) -> Option<Span> {
+  node.descendants().find_map(|n| match n.value() {
+    RawData::One(_, span) => Some(*span),
+    _ => None,
+  })
+}
+
+/// 返回 [None] 表示没填
+fn check_node(database: &Database, typ_id: usize, node: NodeRef<RawData>) -> Result<Option<Value>> {
+  let ty = database.get_type(typ_id).ok_or(类型不存在)?;
+  let span = first_span(node);
+
+  let value = match ty {
+    Type::Unknown => return Err(类型未知.into()),
+    Type::Placeholder(_) => return Err(类型没有定义.into()),
+    Type::Dynamic => unimplemented!("目前不支持动态类型"),
+    Type::Alias { target, .. } => return check_node(database, *target, node),
+    Type::Int
+    | Type::Float
+    | Type::String
+    | Type::Bool
+    | Type::DateTime
+    | Type::Date
+    | Type::Duration
+    | Type::Fixed { .. } => {
+      let RawData::One(s, span) = node.value() else {
+        return Err(原始数据节点类型不匹配.into());
+      };
+      check_cell(database, ty, s, *span)?
+    }
+    Type::Optional(tid) => {
+      Some(check_node(database, *tid, node)?.unwrap_or(Value::new(ValueKind::Null, span)))
+    }
+    // 单独一格里写的列表或元组, 结构体不能写在一格里
+    Type::List(_) | Type::Tuple { .. } | Type::Struct { .. } if node.value().is_one() => {
+      let RawData::One(s, span) = node.value() else {
+        unreachable!()
+      };
+      check_literal(database, typ_id, s, *span)?
+    }
+    Type::Tuple { items, .. } => {
+      if !node.value().is_many() {
+        return Err(原始数据节点类型不匹配.into());
+      }
+      let children = node.children().collect::<Vec<_>>();
+      let cells = children
+        .iter()
+        .map(|ch| match ch.value() {
+          RawData::One(s, span) => Ok((s, *span)),
+          _ => Err(原始数据节点类型不匹配),
+        })
+        .try_collect::<Vec<_>>()?;
+      let only_first = !cells.is_empty()
+        && !cells[0].0.trim().is_empty()
+        && cells[1..].iter().all(|c| c.0.trim().is_empty());
+      // 一个元素的元组要写成 `(x)` 或 `(x,)`, 不然分不清是元组还是元素本身
+      let one_literal = items.len() == 1
+        && cells.len() == 1
+        && cells[0].0.trim().starts_with('(')
+        && cells[0].0.trim().ends_with(')');
+      if (items.len() != 1 && only_first) || one_literal {
+        // 只写了第一格, 按字面量解析
+        check_literal(database, typ_id, cells[0].0, cells[0].1)?
+      } else if children.len() == items.len() {
+        let mut v = Vec::new();
+        for (ch, tid) in children.iter().zip(items.iter()) {
+          v.push(check_node(database, *tid, *ch)?);
+        }
+        collect_tuple(v, span)?
+      } else {
+        return Err(with_span(
+          元组元素数量不匹配(items.len(), children.len()).into(),
+          span,
+        ));
+      }
+    }
+    Type::List(tid) => {
+      if !node.value().is_many() {
+        return Err(原始数据节点类型不匹配.into());
+      }
+      let mut v = Vec::new();
+      for ch in node.children() {
+        if let Some(item) = check_node(database, *tid, ch)? {
+          v.push(item);
+        }
+      }
+      Some(Value::new(ValueKind::List(v), span))
+    }
+    Type::Dict(key_tid, value_tid) => {
+      if !node.value().is_many() {
+        return Err(原始数据节点类型不匹配.into());
+      }
+      let mut v = Vec::new();
+      for ch in node.children() {
+        let mut entry = ch.children();
+        let key_node = entry.next().ok_or(原始数据节点类型不匹配)?;
+        let value_node = entry.next().ok_or(原始数据节点类型不匹配)?;
+        if entry.next().is_some() {
+          return Err(原始数据节点类型不匹配.into());
+        }
+        if let Some(key) = check_node(database, *key_tid, key_node)? {
+          let value = check_node(database, *value_tid, value_node)?
+            .ok_or_else(|| with_span(值为空.into(), first_span(value_node)))?;
+          v.push((key, value));
+        }
+      }
+      Some(Value::new(ValueKind::Map(v), span))
+    }
+    Type::Struct {
+      full_name,
+      fields,
+      meta,
+    } => {
+      let fields_data = node
+        .value()
+        .try_as_struct_ref()
+        .ok_or(原始数据节点类型不匹配)?;
+      if fields.len() != fields_data.len() {
+        return Err(原始数据节点类型不匹配.into());
+      }
+      let mut v = IndexMap::new();
+      for (field_name, f_tid) in fields.iter() {
+        let f_id = fields_data.get(field_name).ok_or(原始数据节点类型不匹配)?;
+        let f_node = node.tree().get(*f_id).ok_or(原始数据节点不存在)?;
+        let Some(mut field) = check_node(database, *f_tid, f_node)? else {
+          return Ok(None);
+        };
+        if let Some(target) = meta
+          .get(field_name)
+          .and_then(|m| m.annotations.get(reference::REF_ANNOTATION))
+        {
+          // 行类型的全名是 `表名.表名_item`
+          let target = database.ref_target(config::path_parent(full_name), field_name, target)?;
+          field = into_ref(field, &Arc::from(target));
+        }
+        v.insert(field_name.clone(), field);
+      }
+      Some(Value::new(ValueKind::Struct(v), span))
+    }
+  };
+  Ok(value)
+}
+
+/// 主键包成 [ValueKind::Ref], 列表里的每个都包
+fn into_ref(value: Value, table: &Arc<str>) -> Value {
+  let kind = match value.kind {
+    ValueKind::Null => ValueKind::Null,
+    ValueKind::List(items) => {
+      ValueKind::List(items.into_iter().map(|v| into_ref(v, table)).collect())
+    }
+    kind => ValueKind::Ref {
+      table: table.clone(),
+      key: Box::new(Value::new(kind, value.span)),
+    },
+  };
+  Value::new(kind, value.span)
+}
+
+fn with_span(e: anyhow::Error, span: Option<Span>) -> anyhow::Error {
+  match span {
+    Some(span) => e.context(format!("{span} 的数据错误")),
+    None => e,
+  }
+}
+
+/// 单个格子里的基础类型
+fn check_cell(database: &Database, ty: &Type, s: &str, span: Span) -> Result<Option<Value>> {
+  if s.trim().is_empty() {
+    return Ok(None);
+  }
+  let parse = || -> Result<ValueKind> {
+    let kind = match ty {
+      Type::Int => {
+        let v = serde_json::from_str::<Number>(s)?;
+        match (v.as_i64(), v.as_u64()) {
+          (Some(v), _) => ValueKind::Int(v),
+          (None, Some(v)) => ValueKind::UInt(v),
+          _ => return Err(数字类型错误.into()),
+        }
+      }
+      Type::Float => {
+        let v = serde_json::from_str::<Number>(s)?;
+        if !v.is_f64() {
+          return Err(数字类型错误.into());
+        }
+        ValueKind::Float(v.as_f64().unwrap())
+      }
+      Type::String => ValueKind::String(serde_json::from_str::<String>(s).unwrap_or(s.to_string())),
+      Type::Bool => ValueKind::Bool(serde_json::from_str::<bool>(s)?),
+      Type::DateTime => {
+        ValueKind::DateTime(datetime::parse_datetime(s, database.options.timezone)?)
+      }
+      Type::Date => ValueKind::Date(datetime::parse_date(s)?),
+      Type::Duration => ValueKind::Duration(datetime::parse_duration(s)?),
+      &Type::Fixed {
+        int_bits,
+        frac_bits,
+      } => ValueKind::Fixed(fixed::parse_fixed(
+        s,
+        int_bits,
+        frac_bits,
+        database.options.rounding,
+      )?),
+      _ => return Err(原始数据节点类型不匹配.into()),
+    };
+    Ok(kind)
+  };
+  let kind = parse().map_err(|e| with_span(e, Some(span)))?;
+  Ok(Some(Value::new(kind, Some(span))))
+}
+
+/// 写在一个格子里的字面量, 例: `(1, 2)`, `[1, 2, 3]`, `1,2`
+fn check_literal(database: &Database, typ_id: usize, s: &str, span: Span) -> Result<Option<Value>> {
+  let ty = database.get_type(typ_id).ok_or(类型不存在)?;
+  let value = match ty {
+    Type::List(tid) => {
+      let mut v = Vec::new();
+      if !s.trim().is_empty() {
+        for item in parser::split_literal(s).map_err(|e| with_span(e, Some(span)))? {
+          if let Some(item) = check_literal(database, *tid, item, span)? {
+            v.push(item);
+          }
+        }
+      }
+      Some(Value::new(ValueKind::List(v), Some(span)))
+    }
+    Type::Tuple { items, .. } => {
+      if s.trim().is_empty() {
+        return Ok(None);
+      }
+      let mut parts = parser::split_literal(s).map_err(|e| with_span(e, Some(span)))?;
+      // `(x,)`
+      if parts.len() == items.len() + 1 && parts.last() == Some(&"") {
+        parts.pop();
+      }
+      if parts.len() != items.len() {
+        return Err(with_span(
+          元组元素数量不匹配(items.len(), parts.len()).into(),
+          Some(span),
+        ));
+      }
+      let mut v = Vec::new();
+      for (part, tid) in parts.into_iter().zip(items.iter()) {
+        v.push(check_literal(database, *tid, part, span)?);
+      }
+      collect_tuple(v, Some(span))?
+    }
+    Type::Optional(tid) => Some(
+      check_literal(database, *tid, s, span)?.unwrap_or(Value::new(ValueKind::Null, Some(span))),
+    ),
+    Type::Alias { target, .. } => check_literal(database, *target, s, span)?,
+    Type::Dict(..) | Type::Struct { .. } if s.trim().is_empty() => None,
+    Type::Dict(..) | Type::Struct { .. } => {
+      return Err(with_span(不支持内联写法.into(), Some(span)));
+    }
+    _ => check_cell(database, ty, s, span)?,
+  };
+  Ok(value)
+}
+
+/// 元组的元素要么都有, 要么都没有
+fn collect_tuple(items: Vec<Option<Value>>, span: Option<Span>) -> Result<Option<Value>> {
+  if items.iter().all(Option::is_none) {
+    Ok(None)
+  } else if items.iter().all(Option::is_some) {
+    let items = items.into_iter().flatten().collect();
+    Ok(Some(Value::new(ValueKind::Tuple(items), span)))
+  } else {
+    Err(with_span(元组元素缺失.into(), span))
+  }
+}
+
+pub mod error {
+  use thiserror::Error;
+
+  #[derive(Debug, Error)]
+  pub enum Error {
+    #[error("类型不存在")]
+    类型不存在,
+    #[error("类型未知")]
+    类型未知,
+    #[error("类型没有定义")]
+    类型没有定义,
+    #[error("原始数据节点不存在")]
+    原始数据节点不存在,
+    #[error("原始数据节点类型不匹配")]
+    原始数据节点类型不匹配,
+    #[error("数字类型错误")]
+    数字类型错误,
+    #[error("元组元素数量不匹配: 需要 {0} 个, 实际 {1} 个")]
+    元组元素数量不匹配(usize, usize),
+    #[error("元组元素缺失")]
+    元组元素缺失,
+    #[error("不支持内联写法")]
+    不支持内联写法,
+    #[error("值为空")]
+    值为空,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::basic::{database::Database, raw_table::RawTable, value::*};
+  use anyhow::Result;
+
+  #[test]
+  fn test_check_table() -> Result<()> {
+    let mut db = Database::new();
+    db.load_project("./test/proj/")?;
+    let data = db.data.iter().find(|d| d.full_name == ".掉落").unwrap();
+    let ValueKind::Map(rows) = &data.value().unwrap().kind else {
+      panic!()
+    };
+    let (key, row) = &rows[1];
+    assert_eq!(key.kind, ValueKind::Int(2));
+    let ValueKind::Struct(fields) = &row.kind else {
+      panic!()
+    };
+    let reward = &fields["奖励"];
+    assert_eq!(reward.span, Some(Span { row: 3, col: 2 }));
+    let ValueKind::Tuple(items) = &reward.kind else {
+      panic!()
+    };
+    assert_eq!(items[0].kind, ValueKind::Int(1002));
+    assert_eq!(fields["备注"].to_json(), "备注");
+    Ok(())
+  }
+
+  #[test]
+  fn test_one_item_tuple() -> Result<()> {
+    let mut db = Database::new();
+    let grid = [
+      vec!["", "id", "v"],
+      vec!["", "i", "t<i>"],
+      vec!["", "1", "(5)"],
+      vec!["", "2", "(6,)"],
+      vec!["", "3", "7"],
+    ];
+    let did = db.add_table(RawTable::from_grid(".元组", &grid)?)?;
+    let json = db.get_data(did).unwrap().value().unwrap().to_json();
+    assert_eq!(
+      json,
+      serde_json::json!({"1": {"id": 1, "v": [5]}, "2": {"id": 2, "v": [6]}, "3": {"id": 3, "v": [7]}})
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn test_uint_and_ref() -> Result<()> {
+    let mut db = Database::new();
+    db.add_tables(vec![
+      RawTable::from_grid(".物品", &[vec!["", "id"], vec!["", "i"], vec!["", "1"]])?,
+      RawTable::from_grid(
+        ".背包",
+        &[
+          vec!["", "id", "物品", "组合", "大数"],
+          vec!["", "i", "i", "l<i>", "i"],
+          vec!["标注", "", "ref=物品", "ref=.物品", ""],
+          vec!["", "1", "1", "1", "18446744073709551615"],
+        ],
+      )?,
+    ])?;
+    let row = db.table(".背包").unwrap().get(1)?.unwrap();
+    let ValueKind::Ref { table, key } = &row.get("物品")?.kind else {
+      panic!("{:?}", row.get("物品")?)
+    };
+    assert_eq!((&**table, &key.kind), (".物品", &ValueKind::Int(1)));
+    assert!(row.get_list("组合")?[0].kind.is_ref());
+    assert_eq!(row.get_int("物品")?, 1);
+    assert_eq!(row.get_uint("大数")?, u64::MAX);
+    assert_eq!(
+      row.value().to_json(),
+      serde_json::json!({"id": 1, "物品": 1, "组合": [1], "大数": u64::MAX})
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn test_check_error_span() {
+    let mut db = Database::new();
+    let grid = [vec!["", "id", "v"], vec!["", "i", "i"], vec!["", "1", "x"]];
+    let raw_table = RawTable::from_grid(".错误", &grid).unwrap();
+    let err = db.add_table(raw_table).unwrap_err();
+    assert!(format!("{err:#}").contains("第 3 行第 3 列"), "{err:#}");
+  }
+}
@@ -1,8 +1,8 @@
-#![feature(error_generic_member_access)]
 #![feature(iterator_try_collect)]
-#![feature(path_add_extension)]
-
 
 pub mod basic;
 
 pub type HashMap<K, V> = std::collections::HashMap<K, V>;
+pub type HashSet<K> = std::collections::HashSet<K>;
+/// 保持插入顺序, 用在会影响输出顺序的地方
+pub type IndexMap<K, V> = indexmap::IndexMap<K, V>;
@@ -1,22 +1,112 @@
-use std::{collections::HashMap, env, path::{self, Path, PathBuf}};
+use std::{
+  fs,
+  path::{self, PathBuf},
+};
 
+use anyhow::Result;
+use chrono::FixedOffset;
 use clap::Parser;
 use log::*;
-use rust_table_export_simple::basic::database::Database;
-use anyhow::Result;
+use rust_table_export_simple::basic::{
+  code_generator::{
+    Registry,
+    csharp::{CSharpGenerator, CSharpOptions},
+    typescript::TypeScriptGenerator,
+  },
+  config::{DictLayout, Options},
+  database::Database,
+  fixed::Rounding,
+};
 
-fn main() -> Result<()>{
+fn main() -> Result<()> {
   env_logger::Builder::from_default_env()
     .filter_level(LevelFilter::Info)
     .init();
   let args = Args::parse();
   dbg!(&args);
-  dbg!(path::absolute(&args.proj));
+  dbg!(path::absolute(&args.proj)?);
+
+  let mut registry = Registry::with_builtin();
+  if let Some(dir) = &args.template_dir
+    && !dir.is_dir()
+  {
+    anyhow::bail!("模板目录不存在: {}", dir.display());
+  }
+  // 没有这个语言的子目录就全用内置的
+  let template_dir = |lang: &str| {
+    let dir = args.template_dir.as_ref()?.join(lang);
+    if dir.is_dir() {
+      info!("{lang} 的模板用 {} 里的覆盖", dir.display());
+      Some(dir)
+    } else {
+      info!("{lang} 的模板全用内置的, 没有 {}", dir.display());
+      None
+    }
+  };
+  registry.register(CSharpGenerator {
+    options: CSharpOptions {
+      template_dir: template_dir("csharp"),
+      ..CSharpOptions::default()
+    },
+  });
+  registry.register(TypeScriptGenerator {
+    template_dir: template_dir("typescript"),
+  });
+  for option in &args.lang_opt {
+    registry.set_option(option)?;
+  }
 
-  let mut db = Database::new();
+  let options = Options {
+    timezone: args.timezone,
+    rounding: args.rounding,
+    stream_threshold: args.stream_threshold,
+    dict_layout: args
+      .dict_layout
+      .or_else(|| {
+        args
+          .lang
+          .iter()
+          .find_map(|lang| registry.get(lang)?.dict_layout())
+      })
+      .unwrap_or_default(),
+  };
+  let mut db = Database::with_options(options);
   db.load_project(args.proj)?;
-  db.generate_data(args.data)?;
-  db.generate_code(args.code)?;
+  let code_targets = args
+    .code
+    .as_ref()
+    .map(|code| {
+      args
+        .lang
+        .iter()
+        .map(|lang| {
+          let dir = match args.lang_out.iter().find(|(l, _)| l == lang) {
+            Some((_, dir)) => dir.clone(),
+            None if args.lang.len() == 1 => code.clone(),
+            None => code.join(lang),
+          };
+          (lang.clone(), dir)
+        })
+        .collect::<Vec<_>>()
+    })
+    .unwrap_or_default();
+  if args.schema.is_some() || !args.template.is_empty() {
+    let schema = db.schema();
+    if let Some(path) = &args.schema {
+      fs::write(path, serde_json::to_string_pretty(&schema)?)?;
+    }
+    for (template, out) in &args.template {
+      let content = schema.render(&fs::read_to_string(template)?)?;
+      fs::write(out, content)?;
+    }
+  }
+  // 数据和代码互不影响, 一起生成
+  let (data_res, code_res) = rayon::join(
+    || args.data.map_or(Ok(()), |data| db.generate_data(data)),
+    || db.generate_code(&registry, &code_targets),
+  );
+  data_res?;
+  code_res?;
   Ok(())
 }
 
@@ -24,6 +114,49 @@ fn main() -> Result<()>{
 struct Args {
   #[arg(long, default_value = "..")]
   proj: PathBuf,
-  data: PathBuf,
-  code: PathBuf,
-}
\ No newline at end of file
+  /// 没写时区的 datetime 按这个时区解释, 例: `+08:00`
+  #[arg(long, default_value = "+00:00")]
+  timezone: FixedOffset,
+  /// 定点数的舍入方式: nearest, nearest_even, floor, ceil, toward_zero, exact
+  #[arg(long, default_value = "nearest")]
+  rounding: Rounding,
+  /// csv 文件超过这么多字节就分块读写, 不整张读进内存
+  #[arg(long)]
+  stream_threshold: Option<u64>,
+  /// 字典在 json 里的写法: object, entries. 默认 `csharp.profile=unity` 下是 entries, 其他是 object
+  #[arg(long)]
+  dict_layout: Option<DictLayout>,
+  /// 生成哪些语言的代码, 例: `--lang csharp,typescript`
+  #[arg(long, value_delimiter = ',', default_value = "csharp")]
+  lang: Vec<String>,
+  /// 某个语言单独的输出目录, 例: `--lang-out typescript=../web/src/cfg`.
+  /// 没写的语言只选了一个时输出到 `code`, 选了多个时输出到 `code/语言名`
+  #[arg(long, value_parser = parse_key_value::<String, PathBuf>)]
+  lang_out: Vec<(String, PathBuf)>,
+  /// 生成器自己的选项, 例: `--lang-opt csharp.alias=using`, 可以写多次. 只写选项名时值是 true.
+  /// csharp 的选项:
+  /// alias (inline, using, struct),
+  /// mutability (mutable, read_only, immutable),
+  /// source_gen, lazy, async, table_class, hot_reload,
+  /// class_case, field_case, namespace_case (keep, pascal, camel, snake),
+  /// profile (dotnet, unity), unity_json (newtonsoft, json_utility), scriptable_object
+  #[arg(long)]
+  lang_opt: Vec<String>,
+  /// 项目自己的模板, 按语言分子目录, 例: `templates/csharp/class.hbs`. 只覆盖同名的内置模板
+  #[arg(long)]
+  template_dir: Option<PathBuf>,
+  /// 把表的结构导出成 json, 给别的工具或模板用
+  #[arg(long)]
+  schema: Option<PathBuf>,
+  /// 用表的结构渲染模板, 例: `--template ts.hbs=out/cfg.ts`, 可以写多次
+  #[arg(long, value_parser = parse_key_value::<PathBuf, PathBuf>)]
+  template: Vec<(PathBuf, PathBuf)>,
+  data: Option<PathBuf>,
+  code: Option<PathBuf>,
+}
+
+/// `a=b`
+fn parse_key_value<K: From<String>, V: From<String>>(s: &str) -> Result<(K, V), String> {
+  let (k, v) = s.split_once('=').ok_or("格式是 `名字=路径`")?;
+  Ok((k.to_string().into(), v.to_string().into()))
+}